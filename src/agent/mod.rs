@@ -0,0 +1,548 @@
+#[cfg(test)]
+mod tests;
+
+use crate::loader::FileSource;
+use crate::{
+    AmbiguousProfile, AwsCredentials, AwsProfile, AwsProfileLookup, Error, ProfileNotFound,
+    Suggestion,
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::fs::{DirBuilder, Permissions};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{Mutex, Notify};
+
+use zeroize::Zeroizing;
+
+/// The environment variable pointing clients at a running agent's socket.
+pub const SOCKET_ENV_VAR: &str = "AWS_ENV_AGENT_SOCK";
+/// The environment variable holding the process id of a running agent.
+pub const PID_ENV_VAR: &str = "AWS_ENV_AGENT_PID";
+
+/// Initial capacity for buffers that will hold secrets, large enough that they never reallocate and leave copies
+/// of credentials behind in freed memory.
+const SECRET_BUFFER_CAPACITY: usize = 16 * 1024;
+
+/// A request sent from a client to the agent, one JSON document per line.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum Request {
+    /// Resolve a profile by URI or bare name, refusing ambiguous bare names if `strict`.
+    Get {
+        name: String,
+        #[serde(default)]
+        strict: bool,
+    },
+    /// Drop all cached profiles, forcing the next request to decrypt again.
+    Flush,
+    /// Shut the agent down.
+    Stop,
+}
+
+/// A response sent from the agent to a client, one JSON document per line.
+///
+/// The profile type is generic so that the agent can serialize borrowed records straight from its cache while
+/// clients deserialize into owned values.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+enum Response<P> {
+    Profile {
+        profile: P,
+    },
+    NotFound {
+        #[serde(default)]
+        suggestions: Vec<Suggestion>,
+    },
+    Ambiguous {
+        uris: Vec<String>,
+    },
+    Done,
+    Error {
+        message: String,
+    },
+}
+
+/// A borrowed view of an `AwsProfile` for writing to the socket.
+#[derive(Serialize)]
+struct ProfileRecord<'a> {
+    name: &'a str,
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    session_token: Option<&'a str>,
+    region: Option<&'a str>,
+    expiration: Option<String>,
+    path: &'a Path,
+    encrypted: bool,
+}
+
+impl<'a> From<&'a AwsProfile> for ProfileRecord<'a> {
+    fn from(profile: &'a AwsProfile) -> Self {
+        Self {
+            name: profile.name.as_str(),
+            access_key_id: profile.access_key_id.as_str(),
+            secret_access_key: profile.secret_access_key.as_str(),
+            session_token: profile.session_token.as_ref().map(|t| t.as_str()),
+            region: profile.region.as_deref(),
+            expiration: profile
+                .expiration
+                .map(|e| humantime::format_rfc3339_seconds(e).to_string()),
+            path: profile.file.path.as_path(),
+            encrypted: profile.file.encrypted,
+        }
+    }
+}
+
+/// An owned profile as read from the socket, converted into an `AwsProfile` without copying the secrets.
+#[derive(Deserialize)]
+struct ProfileMessage {
+    name: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    region: Option<String>,
+    expiration: Option<String>,
+    path: PathBuf,
+    encrypted: bool,
+}
+
+impl From<ProfileMessage> for AwsProfile {
+    fn from(message: ProfileMessage) -> Self {
+        AwsProfile {
+            name: message.name,
+            access_key_id: message.access_key_id,
+            secret_access_key: Zeroizing::new(message.secret_access_key),
+            session_token: message.session_token.map(Zeroizing::new),
+            region: message.region,
+            expiration: message
+                .expiration
+                .and_then(|e| humantime::parse_rfc3339(e.as_str()).ok()),
+            file: FileSource {
+                path: message.path,
+                encrypted: message.encrypted,
+            },
+        }
+    }
+}
+
+/// Decrypted profiles held by the agent along with the time they were loaded.
+struct Cache {
+    lookup: AwsProfileLookup,
+    loaded: Instant,
+}
+
+/// A long-running process that caches decrypted profiles in memory and serves them over a Unix socket.
+///
+/// Profiles are loaded lazily on the first request and dropped (and thereby zeroized) once the TTL has elapsed,
+/// after which the next request will decrypt all credential files again. Only processes running as the same user
+/// as the agent may connect.
+pub struct Agent {
+    socket: PathBuf,
+    ttl: Duration,
+    cache: Mutex<Option<Cache>>,
+    generation: AtomicU64,
+    shutdown: Notify,
+}
+
+impl Agent {
+    pub fn new<P: AsRef<Path>>(socket: P, ttl: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            socket: socket.as_ref().to_path_buf(),
+            ttl,
+            cache: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            shutdown: Notify::new(),
+        })
+    }
+
+    /// Seed the agent's cache with already-loaded profiles, which expire after the TTL like any others.
+    pub async fn preload(self: &Arc<Self>, lookup: AwsProfileLookup) {
+        let mut cache = self.cache.lock().await;
+        self.store(&mut cache, lookup);
+    }
+
+    pub fn socket(&self) -> &Path {
+        self.socket.as_path()
+    }
+
+    /// Bind the socket and serve requests until asked to stop or until the process receives `SIGINT`/`SIGTERM`.
+    pub async fn serve(self: Arc<Self>) -> Result<(), Error> {
+        let listener = bind(&self.socket).await?;
+
+        log::info!("Agent listening on {}", self.socket.display());
+
+        let (mut interrupt, mut terminate) = (
+            signal(SignalKind::interrupt())?,
+            signal(SignalKind::terminate())?,
+        );
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    match accepted {
+                        Ok((stream, _)) => {
+                            let agent = self.clone();
+
+                            tokio::spawn(async move {
+                                if let Err(e) = agent.handle(stream).await {
+                                    log::warn!("Unable to serve agent client: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) => log::warn!("Unable to accept agent connection: {}", e),
+                    }
+                }
+                _ = self.shutdown.notified() => break,
+                _ = interrupt.recv() => break,
+                _ = terminate.recv() => break,
+            }
+        }
+
+        log::info!("Agent shutting down.");
+
+        // drop and zeroize everything before exiting
+        self.cache.lock().await.take();
+
+        let _ = std::fs::remove_file(&self.socket);
+
+        Ok(())
+    }
+
+    async fn handle(self: Arc<Self>, stream: UnixStream) -> Result<(), Error> {
+        let peer = stream.peer_cred()?;
+
+        // SAFETY: getuid is always successful and has no side-effects
+        if peer.uid() != unsafe { libc::getuid() } {
+            return Err(format!("refusing connection from uid {}", peer.uid()).into());
+        }
+
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+
+        BufReader::new(reader).read_line(&mut line).await?;
+
+        let request: Request = serde_json::from_str(line.as_str())?;
+
+        let output = match request {
+            Request::Get { name, strict } => {
+                log::debug!("Agent resolving profile '{}'", name);
+
+                let mut cache = self.cache.lock().await;
+
+                match self.lookup(&mut cache).await {
+                    Ok(lookup) => {
+                        let found = if strict {
+                            lookup.find_strict(name.as_str())
+                        } else {
+                            lookup.find(name.as_str()).map_err(Error::from)
+                        };
+
+                        match found {
+                            Ok(profile) => encode(&Response::Profile {
+                                profile: ProfileRecord::from(profile),
+                            })?,
+                            Err(e) => match e.downcast::<ProfileNotFound>() {
+                                Ok(e) => encode(&Response::<ProfileRecord>::NotFound {
+                                    suggestions: e.suggestions,
+                                })?,
+                                Err(e) => match e.downcast::<AmbiguousProfile>() {
+                                    Ok(e) => encode(&Response::<ProfileRecord>::Ambiguous {
+                                        uris: e.uris,
+                                    })?,
+                                    Err(e) => encode(&Response::<ProfileRecord>::Error {
+                                        message: e.to_string(),
+                                    })?,
+                                },
+                            },
+                        }
+                    }
+                    Err(e) => encode(&Response::<ProfileRecord>::Error {
+                        message: e.to_string(),
+                    })?,
+                }
+            }
+            Request::Flush => {
+                log::info!("Agent flushing cached profiles.");
+                self.cache.lock().await.take();
+                encode(&Response::<ProfileRecord>::Done)?
+            }
+            Request::Stop => {
+                self.shutdown.notify_one();
+                encode(&Response::<ProfileRecord>::Done)?
+            }
+        };
+
+        writer.write_all(output.as_slice()).await?;
+        writer.shutdown().await?;
+
+        Ok(())
+    }
+
+    /// Return the cached profiles, decrypting all credential files if the cache is empty or has expired.
+    async fn lookup<'c>(
+        self: &Arc<Self>,
+        cache: &'c mut Option<Cache>,
+    ) -> Result<&'c AwsProfileLookup, Error> {
+        if matches!(cache, Some(c) if c.loaded.elapsed() >= self.ttl) {
+            log::info!("Cached profiles have expired.");
+            cache.take();
+        }
+
+        if cache.is_none() {
+            log::info!("Loading all credential files.");
+
+            let credentials = AwsCredentials::load_all()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let mut lookup = AwsProfileLookup::new();
+            lookup.insert_all(credentials.sources.into_iter());
+
+            self.store(cache, lookup);
+        }
+
+        Ok(&cache.as_ref().unwrap().lookup)
+    }
+
+    /// Place profiles in the cache, locking their secrets in memory and scheduling their expiry.
+    fn store(self: &Arc<Self>, cache: &mut Option<Cache>, lookup: AwsProfileLookup) {
+        for profile in lookup.list() {
+            lock_memory(profile.secret_access_key.as_bytes());
+
+            if let Some(token) = &profile.session_token {
+                lock_memory(token.as_bytes());
+            }
+        }
+
+        *cache = Some(Cache {
+            lookup,
+            loaded: Instant::now(),
+        });
+
+        // expire proactively so that secrets don't linger in memory when no further requests arrive
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let agent = self.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(agent.ttl).await;
+
+            if agent.generation.load(Ordering::SeqCst) == generation {
+                log::info!("Cached profiles have expired.");
+                agent.cache.lock().await.take();
+            }
+        });
+    }
+}
+
+/// Client for a running `Agent`.
+pub struct AgentClient {
+    socket: PathBuf,
+    strict: bool,
+}
+
+impl AgentClient {
+    pub fn new<P: AsRef<Path>>(socket: P) -> Self {
+        Self {
+            socket: socket.as_ref().to_path_buf(),
+            strict: false,
+        }
+    }
+
+    /// Have the agent refuse bare names matching profiles in more than one file, see
+    /// [AwsProfileLookup::find_strict].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Create a client from `AWS_ENV_AGENT_SOCK`, if set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os(SOCKET_ENV_VAR)
+            .filter(|s| !s.is_empty())
+            .map(Self::new)
+    }
+
+    pub fn socket(&self) -> &Path {
+        self.socket.as_path()
+    }
+
+    /// Resolve a profile by URI or bare name through the agent.
+    ///
+    /// If the agent doesn't know the profile, the error is a [ProfileNotFound] with the agent's suggestions, or an
+    /// [AmbiguousProfile] if the client is strict and the name is ambiguous.
+    pub async fn get<S: AsRef<str>>(&self, name: S) -> Result<AwsProfile, Error> {
+        let response = self
+            .request(&Request::Get {
+                name: name.as_ref().to_string(),
+                strict: self.strict,
+            })
+            .await?;
+
+        match serde_json::from_str::<Response<ProfileMessage>>(response.as_str())? {
+            Response::Profile { profile } => Ok(profile.into()),
+            Response::NotFound { suggestions } => Err(ProfileNotFound {
+                name: name.as_ref().to_string(),
+                suggestions,
+            }
+            .into()),
+            Response::Ambiguous { uris } => Err(AmbiguousProfile {
+                name: name.as_ref().to_string(),
+                uris,
+            }
+            .into()),
+            Response::Done => Err("unexpected response from agent".into()),
+            Response::Error { message } => Err(message.into()),
+        }
+    }
+
+    /// Ask the agent to drop all cached profiles.
+    pub async fn flush(&self) -> Result<(), Error> {
+        self.command(&Request::Flush).await
+    }
+
+    /// Ask the agent to shut down.
+    pub async fn stop(&self) -> Result<(), Error> {
+        self.command(&Request::Stop).await
+    }
+
+    async fn command(&self, request: &Request) -> Result<(), Error> {
+        let response = self.request(request).await?;
+
+        match serde_json::from_str::<Response<serde_json::Value>>(response.as_str())? {
+            Response::Done => Ok(()),
+            Response::Error { message } => Err(message.into()),
+            _ => Err("unexpected response from agent".into()),
+        }
+    }
+
+    async fn request(&self, request: &Request) -> Result<Zeroizing<String>, Error> {
+        let stream = UnixStream::connect(&self.socket).await.map_err(|e| {
+            format!(
+                "unable to connect to agent at {}: {}",
+                self.socket.display(),
+                e
+            )
+        })?;
+
+        let (reader, mut writer) = stream.into_split();
+
+        let mut payload = serde_json::to_vec(request)?;
+        payload.push(b'\n');
+
+        writer.write_all(payload.as_slice()).await?;
+
+        // NOTE pre-allocate so that the response, which may contain secrets, is never reallocated
+        let mut response = Zeroizing::new(String::with_capacity(SECRET_BUFFER_CAPACITY));
+        BufReader::new(reader).read_line(&mut response).await?;
+
+        if response.is_empty() {
+            return Err("agent closed the connection without responding".into());
+        }
+
+        Ok(response)
+    }
+}
+
+/// Get the default socket path, inside a private directory under `$XDG_RUNTIME_DIR` or the temporary directory.
+pub fn default_socket_path() -> PathBuf {
+    let base = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+
+    // SAFETY: getuid is always successful and has no side-effects
+    base.join(format!("aws-env-{}", unsafe { libc::getuid() }))
+        .join("agent.sock")
+}
+
+/// Prevent the current process from writing core dumps or being attached to, as it will hold decrypted secrets.
+pub fn harden_process() {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    // SAFETY: the rlimit struct is fully initialized and outlives the call
+    if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+        log::warn!("Unable to disable core dumps for the agent process.");
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: PR_SET_DUMPABLE takes a single integer argument
+        if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } != 0 {
+            log::warn!("Unable to mark the agent process as non-dumpable.");
+        }
+    }
+}
+
+/// Serialize a response into a single line, in a buffer that is zeroized on drop.
+fn encode<P: Serialize>(response: &Response<P>) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut buffer = Zeroizing::new(Vec::with_capacity(SECRET_BUFFER_CAPACITY));
+
+    serde_json::to_writer(&mut *buffer, response)?;
+    buffer.push(b'\n');
+
+    Ok(buffer)
+}
+
+/// Lock the pages backing the given bytes into memory so that they are never written to swap.
+///
+/// Failure is not fatal, as the default `RLIMIT_MEMLOCK` may be low; a warning is logged instead.
+fn lock_memory(bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    // SAFETY: the pointer and length describe a live allocation owned by the caller
+    if unsafe { libc::mlock(bytes.as_ptr() as *const libc::c_void, bytes.len()) } != 0 {
+        log::warn!(
+            "Unable to lock secrets in memory: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Bind a listener at the given path, creating a private parent directory and refusing to replace a live agent.
+async fn bind(socket: &Path) -> Result<UnixListener, Error> {
+    let parent = socket
+        .parent()
+        .ok_or_else(|| format!("invalid socket path {}", socket.display()))?;
+
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(parent)?;
+
+    let metadata = std::fs::metadata(parent)?;
+
+    // SAFETY: getuid is always successful and has no side-effects
+    if metadata.uid() != unsafe { libc::getuid() } || metadata.mode() & 0o077 != 0 {
+        return Err(format!(
+            "refusing to use socket directory {} as it is not private to the current user",
+            parent.display()
+        )
+        .into());
+    }
+
+    if socket.exists() {
+        if UnixStream::connect(socket).await.is_ok() {
+            return Err(format!("an agent is already listening on {}", socket.display()).into());
+        }
+
+        log::debug!("Removing stale agent socket {}", socket.display());
+        std::fs::remove_file(socket)?;
+    }
+
+    let listener = UnixListener::bind(socket)?;
+    std::fs::set_permissions(socket, Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
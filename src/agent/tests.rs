@@ -0,0 +1,145 @@
+use super::{Agent, AgentClient};
+use crate::loader::FileSource;
+use crate::{AwsCredentialsFile, AwsProfile, AwsProfileLookup, ProfileNotFound, Suggestion};
+
+use indexmap::IndexMap;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use zeroize::Zeroizing;
+
+fn socket_path(test: &str) -> PathBuf {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+
+    std::env::temp_dir()
+        .join(format!("aws-env-test-{}-{}", test, nonce))
+        .join("agent.sock")
+}
+
+fn lookup() -> AwsProfileLookup {
+    let mut file = AwsCredentialsFile {
+        file: FileSource::from_path(
+            crate::utils::homedir()
+                .join(".aws")
+                .join("credentials.d")
+                .join("work.asc"),
+        ),
+        profiles: IndexMap::new(),
+    };
+
+    file.push(AwsProfile {
+        name: "prod".into(),
+        access_key_id: "AKIAEXAMPLE".into(),
+        secret_access_key: Zeroizing::new("secret".into()),
+        session_token: Some(Zeroizing::new("token".into())),
+        region: Some("eu-west-1".into()),
+        expiration: Some(UNIX_EPOCH + Duration::from_secs(1_800_000_000)),
+        file: file.file.clone(),
+    });
+
+    let mut lookup = AwsProfileLookup::new();
+    lookup.insert(file);
+    lookup
+}
+
+#[tokio::test]
+async fn test_serves_profiles() {
+    let socket = socket_path("serve");
+    let agent = Agent::new(&socket, Duration::from_secs(60));
+    agent.preload(lookup()).await;
+
+    let server = tokio::spawn(agent.clone().serve());
+
+    while !socket.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // the socket and its directory must only be accessible by the current user
+    assert_eq!(
+        0o600,
+        std::fs::metadata(&socket).unwrap().permissions().mode() & 0o777
+    );
+    assert_eq!(
+        0o700,
+        std::fs::metadata(socket.parent().unwrap())
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777
+    );
+
+    let client = AgentClient::new(&socket);
+
+    for name in ["prod", "work/prod"] {
+        let profile = client.get(name).await.unwrap();
+
+        assert_eq!("prod", profile.name.as_str());
+        assert_eq!("work/prod", profile.uri());
+        assert_eq!("AKIAEXAMPLE", profile.access_key_id.as_str());
+        assert_eq!("secret", profile.secret_access_key.as_str());
+        assert_eq!("token", profile.session_token.as_ref().unwrap().as_str());
+        assert_eq!(Some("eu-west-1"), profile.region.as_deref());
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(1_800_000_000)),
+            profile.expiration
+        );
+        assert!(profile.file.encrypted);
+    }
+
+    // unknown profiles come back with the agent's suggestions
+    let error = client.get("prd").await.unwrap_err();
+    let not_found = error.downcast_ref::<ProfileNotFound>().unwrap();
+
+    assert_eq!("prd", not_found.name.as_str());
+    assert_eq!(
+        vec![Suggestion {
+            name: "prod".into(),
+            uris: vec!["work/prod".into()]
+        }],
+        not_found.suggestions
+    );
+
+    client.stop().await.unwrap();
+    server.await.unwrap().unwrap();
+
+    assert!(!socket.exists());
+    let _ = std::fs::remove_dir(socket.parent().unwrap());
+}
+
+#[tokio::test]
+async fn test_refuses_second_agent() {
+    let socket = socket_path("twice");
+    let agent = Agent::new(&socket, Duration::from_secs(60));
+    let server = tokio::spawn(agent.clone().serve());
+
+    while !socket.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    assert!(Agent::new(&socket, Duration::from_secs(60))
+        .serve()
+        .await
+        .is_err());
+
+    AgentClient::new(&socket).stop().await.unwrap();
+    server.await.unwrap().unwrap();
+
+    let _ = std::fs::remove_dir(socket.parent().unwrap());
+}
+
+#[tokio::test]
+async fn test_cache_expires() {
+    let agent = Agent::new(socket_path("expire"), Duration::from_millis(20));
+    agent.preload(lookup()).await;
+
+    assert!(agent.cache.lock().await.is_some());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(agent.cache.lock().await.is_none());
+}
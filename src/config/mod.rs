@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests;
+
+use crate::export::{Dialect, Template};
+use crate::ini::{self, DiagnosticKind};
+use crate::{utils, Error};
+
+use indexmap::IndexMap;
+
+use std::io;
+use std::path::PathBuf;
+
+/// The environment variable overriding the location of the configuration file.
+pub const CONFIG_ENV_VAR: &str = "AWS_ENV_CONFIG";
+
+/// The section holding general settings.
+const SETTINGS_SECTION: &str = "settings";
+
+/// The prefix of sections defining export templates, e.g. `[template terraform]`.
+const TEMPLATE_SECTION_PREFIX: &str = "template ";
+
+/// The template key selecting the dialect, all other keys being profile fields.
+const DIALECT_KEY: &str = "dialect";
+
+/// Settings read from aws-env's own INI configuration file, `~/.aws/aws-env.ini` unless overridden.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Config {
+    /// Whether bare profile names matching profiles in more than one file are refused rather than resolved to the
+    /// highest priority profile.
+    pub strict: bool,
+    /// User-defined export templates by name, in the order they are defined.
+    pub templates: IndexMap<String, Template>,
+}
+
+impl Config {
+    /// The path of the configuration file, taken from `$AWS_ENV_CONFIG` if set.
+    pub fn path() -> PathBuf {
+        std::env::var_os(CONFIG_ENV_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| utils::homedir().join(".aws").join("aws-env.ini"))
+    }
+
+    /// Load the configuration file, falling back to the defaults if it doesn't exist.
+    pub async fn load() -> Result<Self, Error> {
+        let path = Self::path();
+
+        match tokio::fs::read_to_string(&path).await {
+            Ok(source) => {
+                Self::parse(&source).map_err(|e| format!("{}: {}", path.display(), e).into())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(format!("unable to read {}: {}", path.display(), e).into()),
+        }
+    }
+
+    /// Parse a configuration file.
+    ///
+    /// General settings are read from the `[settings]` section:
+    ///
+    /// ```ini
+    /// [settings]
+    /// strict = true
+    /// ```
+    ///
+    /// Each `[template NAME]` section defines an export template, with a `dialect` key selecting the syntax and every
+    /// other key mapping a profile field to the variable it is assigned to:
+    ///
+    /// ```ini
+    /// [template terraform]
+    /// dialect = shell
+    /// access_key_id = TF_VAR_aws_access_key
+    /// secret_access_key = TF_VAR_aws_secret_key
+    /// ```
+    pub fn parse<S: AsRef<str> + ?Sized>(source: &S) -> Result<Self, Error> {
+        let mut config = Self::default();
+        let (sections, diagnostics) = ini::parse_with_diagnostics(source);
+
+        for diagnostic in diagnostics {
+            match diagnostic.kind {
+                DiagnosticKind::MalformedLine => {
+                    return Err(format!("malformed line {}", diagnostic.line).into())
+                }
+                _ => log::warn!("Problem parsing configuration at {}", diagnostic),
+            }
+        }
+
+        for (section, properties) in sections {
+            if section == SETTINGS_SECTION {
+                for (key, value) in properties {
+                    match (key.as_ref(), value.as_str()) {
+                        ("strict", Some(value)) => {
+                            config.strict = value.parse().map_err(|_| {
+                                format!("strict must be true or false, not {}", value)
+                            })?
+                        }
+                        _ => log::warn!("Ignoring unknown setting {}", key),
+                    }
+                }
+
+                continue;
+            }
+
+            let name = match section.strip_prefix(TEMPLATE_SECTION_PREFIX) {
+                Some(name) => name.trim(),
+                None => {
+                    log::warn!("Ignoring unknown configuration section [{}]", section);
+                    continue;
+                }
+            };
+
+            let mut template = Template::new(name, Dialect::Shell);
+
+            for (key, value) in properties {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| format!("template {} has no value for {}", name, key))?;
+
+                if key == DIALECT_KEY {
+                    template.dialect = value.parse()?;
+                } else {
+                    template
+                        .push(value, key.parse()?)
+                        .map_err(|e| format!("template {}: {}", name, e))?;
+                }
+            }
+
+            config.templates.insert(name.to_string(), template);
+        }
+
+        Ok(config)
+    }
+}
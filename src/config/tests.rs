@@ -0,0 +1,92 @@
+use super::Config;
+use crate::export::{write_template, Dialect, Field};
+use crate::loader::FileSource;
+use crate::AwsProfile;
+
+const CONFIG: &str = r#"
+[settings]
+strict = true
+
+[template terraform]
+access_key_id = TF_VAR_aws_access_key
+secret_access_key = TF_VAR_aws_secret_key
+session_token = TF_VAR_aws_token
+
+[template s3]
+dialect = docker-env
+uri = S3_PROFILE
+access_key_id = S3_KEY
+secret_access_key = S3_SECRET
+region = S3_REGION
+"#;
+
+#[test]
+fn test_templates() {
+    let config = Config::parse(CONFIG).unwrap();
+
+    assert!(config.strict);
+    assert!(!Config::parse("").unwrap().strict);
+
+    assert_eq!(
+        vec!["terraform", "s3"],
+        config.templates.keys().collect::<Vec<_>>()
+    );
+
+    let terraform = &config.templates["terraform"];
+
+    assert_eq!(Dialect::Shell, terraform.dialect);
+    assert_eq!(
+        vec![
+            ("TF_VAR_aws_access_key".to_string(), Field::AccessKeyId),
+            ("TF_VAR_aws_secret_key".to_string(), Field::SecretAccessKey),
+            ("TF_VAR_aws_token".to_string(), Field::SessionToken),
+        ],
+        terraform.variables
+    );
+
+    let mut profile = AwsProfile::new(
+        "prod",
+        "AKIAEXAMPLE",
+        "a b".into(),
+        None,
+        FileSource::from_path(
+            crate::utils::homedir()
+                .join(".aws")
+                .join("credentials.d")
+                .join("work.ini"),
+        ),
+    );
+
+    // absent fields are left out
+    let mut output = Vec::new();
+    write_template(&mut output, &profile, terraform).unwrap();
+
+    assert_eq!(
+        "export TF_VAR_aws_access_key=AKIAEXAMPLE\nexport TF_VAR_aws_secret_key='a b'\n",
+        String::from_utf8(output).unwrap()
+    );
+
+    profile.region = Some("eu-west-1".into());
+
+    let mut output = Vec::new();
+    write_template(&mut output, &profile, &config.templates["s3"]).unwrap();
+
+    assert_eq!(
+        "S3_PROFILE=work/prod\nS3_KEY=AKIAEXAMPLE\nS3_SECRET=a b\nS3_REGION=eu-west-1\n",
+        String::from_utf8(output).unwrap()
+    );
+}
+
+#[test]
+fn test_invalid_config() {
+    for source in [
+        "[template t]\ndialect = fish\n",
+        "[template t]\npassword = PASSWORD\n",
+        "[template t]\naccess_key_id = NOT-A-NAME\n",
+        "[template t]\naccess_key_id = 1KEY\n",
+        "[template t]\naccess_key_id\n",
+        "[settings]\nstrict = yes\n",
+    ] {
+        assert!(Config::parse(source).is_err(), "{}", source);
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests;
+
+use crate::utils::http::{self, json, status, Handler};
+use crate::{utils, AwsProfile, Error};
+
+use hyper::header::AUTHORIZATION;
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+use serde::Serialize;
+
+use subtle::ConstantTimeEq;
+
+use std::future::Future;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// The environment variable pointing the AWS SDKs at a container credentials endpoint.
+pub const FULL_URI_ENV_VAR: &str = "AWS_CONTAINER_CREDENTIALS_FULL_URI";
+/// The environment variable holding the value the SDKs send in the `Authorization` header.
+pub const AUTHORIZATION_TOKEN_ENV_VAR: &str = "AWS_CONTAINER_AUTHORIZATION_TOKEN";
+
+/// The path credentials are served on.
+pub const CREDENTIALS_PATH: &str = "/creds";
+
+/// Credentials in the format returned by the ECS container credentials endpoint.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Credentials<'a> {
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    token: &'a str,
+    expiration: String,
+}
+
+/// An emulation of the ECS container credentials endpoint serving the credentials of a single profile.
+///
+/// Every request must carry the authorization token in its `Authorization` header, which the SDKs do automatically
+/// when `AWS_CONTAINER_AUTHORIZATION_TOKEN` is set alongside `AWS_CONTAINER_CREDENTIALS_FULL_URI`.
+pub struct Ecs {
+    profile: AwsProfile,
+    token: String,
+    lifetime: Duration,
+}
+
+impl Ecs {
+    /// Create a service for the given profile with a randomly generated authorization token.
+    ///
+    /// The `lifetime` is the expiration advertised to clients, after which they will request credentials again.
+    pub fn new(profile: AwsProfile, lifetime: Duration) -> Result<Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            profile,
+            token: utils::random_token(32)?,
+            lifetime,
+        }))
+    }
+
+    /// The token clients must present in the `Authorization` header.
+    pub fn token(&self) -> &str {
+        self.token.as_str()
+    }
+
+    /// Serve requests on the given listener until the `shutdown` future completes.
+    pub async fn serve<F: Future<Output = ()>>(
+        self: Arc<Self>,
+        listener: TcpListener,
+        shutdown: F,
+    ) -> Result<(), Error> {
+        http::serve(self, listener, shutdown).await
+    }
+
+    fn is_authorized(&self, request: &Request<Body>) -> bool {
+        request
+            .headers()
+            .get(AUTHORIZATION)
+            // NOTE compared in constant time, so that response times give nothing away about the token
+            .map(|v| bool::from(v.as_bytes().ct_eq(self.token.as_bytes())))
+            .unwrap_or(false)
+    }
+
+    fn credentials(&self) -> Response<Body> {
+        let credentials = Credentials {
+            access_key_id: self.profile.access_key_id.as_str(),
+            secret_access_key: self.profile.secret_access_key.as_str(),
+            token: self
+                .profile
+                .session_token
+                .as_ref()
+                .map(|t| t.as_str())
+                .unwrap_or_default(),
+            expiration: humantime::format_rfc3339_seconds(SystemTime::now() + self.lifetime)
+                .to_string(),
+        };
+
+        json(&credentials, "application/json")
+    }
+}
+
+impl Handler for Ecs {
+    fn respond(&self, request: &Request<Body>) -> Response<Body> {
+        if request.method() != Method::GET {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        if !self.is_authorized(request) {
+            return status(StatusCode::UNAUTHORIZED);
+        }
+
+        if request.uri().path() == CREDENTIALS_PATH {
+            self.credentials()
+        } else {
+            status(StatusCode::NOT_FOUND)
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use super::{Ecs, CREDENTIALS_PATH};
+use crate::loader::FileSource;
+use crate::AwsProfile;
+
+use hyper::body::to_bytes;
+use hyper::header::AUTHORIZATION;
+use hyper::{Body, Client, Request, StatusCode};
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+#[tokio::test]
+async fn test_authorization() {
+    let ecs = Ecs::new(
+        AwsProfile::new(
+            "prod",
+            "AKIAEXAMPLE",
+            "secret".into(),
+            None,
+            FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+        ),
+        Duration::from_secs(3600),
+    )
+    .unwrap();
+
+    let token = ecs.token().to_string();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+
+    let server = tokio::spawn(ecs.serve(listener, async {
+        stopped.await.ok();
+    }));
+
+    let client = Client::new();
+
+    let get = |path: &str, authorization: Option<&str>| {
+        let mut builder = Request::get(format!("http://{}{}", address, path));
+
+        if let Some(authorization) = authorization {
+            builder = builder.header(AUTHORIZATION, authorization);
+        }
+
+        client.request(builder.body(Body::empty()).unwrap())
+    };
+
+    let response = get(CREDENTIALS_PATH, None).await.unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let response = get(CREDENTIALS_PATH, Some("wrong")).await.unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+
+    let response = get("/other", Some(token.as_str())).await.unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+    let response = get(CREDENTIALS_PATH, Some(token.as_str())).await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+
+    let body = to_bytes(response.into_body()).await.unwrap();
+    let credentials: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!("AKIAEXAMPLE", credentials["AccessKeyId"]);
+    assert_eq!("secret", credentials["SecretAccessKey"]);
+    assert_eq!("", credentials["Token"]);
+    assert!(humantime::parse_rfc3339(credentials["Expiration"].as_str().unwrap()).is_ok());
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
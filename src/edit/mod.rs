@@ -0,0 +1,672 @@
+#[cfg(test)]
+mod tests;
+
+use crate::ini::Document;
+use crate::loader::credentials_file::{
+    self, INI_ACCESS_KEY_ID_TAG, INI_EXPIRATION_TAGS, INI_REGION_TAG, INI_SECRET_ACCESS_KEY_TAG,
+    INI_SESSION_TOKEN_TAG,
+};
+use crate::loader::BACKUP_EXTENSION;
+use crate::{
+    utils, AtomicFile, AwsCredentials, AwsProfile, Error, FileSource, KeyType, ProfileNotFound,
+};
+
+use serde::Deserialize;
+
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::fs::DirBuilder;
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+
+use zeroize::Zeroizing;
+
+/// The mode of credential files created from scratch.
+const NEW_FILE_MODE: u32 = 0o600;
+/// The mode of `~/.aws` and `~/.aws/credentials.d` when created from scratch.
+const NEW_DIR_MODE: u32 = 0o700;
+
+/// How files are edited.
+#[derive(Clone, Debug, Default)]
+pub struct EditOptions {
+    /// Replace existing profiles.
+    pub force: bool,
+    /// Back up files before replacing them, see `backup`.
+    pub backup: bool,
+    /// The gpg recipients to encrypt encrypted files to, rather than the recipients they are already encrypted to.
+    pub recipients: Vec<String>,
+}
+
+/// A profile which already exists in the file it was to be added to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProfileExists {
+    pub uri: String,
+}
+
+impl Display for ProfileExists {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "profile '{}' already exists", self.uri)
+    }
+}
+
+impl std::error::Error for ProfileExists {}
+
+/// Credentials for a new profile, as read by `Credentials::from_str`.
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: Zeroizing<String>,
+    pub session_token: Option<Zeroizing<String>>,
+    pub expiration: Option<SystemTime>,
+}
+
+impl Credentials {
+    /// A profile by the given name with these credentials, to be added to the given file.
+    pub fn into_profile<S: Into<String>>(self, name: S, file: FileSource) -> AwsProfile {
+        AwsProfile {
+            name: name.into(),
+            access_key_id: self.access_key_id,
+            secret_access_key: self.secret_access_key,
+            session_token: self.session_token,
+            region: None,
+            expiration: self.expiration,
+            file,
+        }
+    }
+}
+
+/// Credentials as output by `aws sts` commands, `aws iam create-access-key` or a `credential_process`, with field
+/// names either in PascalCase or as in credential files.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CredentialsJson {
+    Sts {
+        #[serde(rename = "Credentials")]
+        credentials: CredentialsFields,
+    },
+    Iam {
+        #[serde(rename = "AccessKey")]
+        access_key: CredentialsFields,
+    },
+    Bare(CredentialsFields),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CredentialsFields {
+    #[serde(alias = "aws_access_key_id")]
+    access_key_id: String,
+    #[serde(alias = "aws_secret_access_key")]
+    secret_access_key: String,
+    #[serde(default, alias = "aws_session_token")]
+    session_token: Option<String>,
+    #[serde(default, alias = "aws_expiration")]
+    expiration: Option<String>,
+}
+
+impl FromStr for Credentials {
+    type Err = Error;
+
+    /// Read credentials from either a JSON object or lines holding the access key id, the secret access key and
+    /// optionally a session token, in that order.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim_start().starts_with('{') {
+            let fields = match serde_json::from_str(s)
+                .map_err(|e| format!("unable to parse credentials: {}", e))?
+            {
+                CredentialsJson::Sts { credentials } => credentials,
+                CredentialsJson::Iam { access_key } => access_key,
+                CredentialsJson::Bare(fields) => fields,
+            };
+
+            Ok(Self {
+                access_key_id: fields.access_key_id,
+                secret_access_key: Zeroizing::new(fields.secret_access_key),
+                session_token: fields.session_token.map(Zeroizing::new),
+                expiration: fields
+                    .expiration
+                    .map(|e| {
+                        // NOTE the AWS CLI writes UTC as an offset, which humantime doesn't accept
+                        let utc = e.strip_suffix("+00:00").map(|e| format!("{}Z", e));
+
+                        humantime::parse_rfc3339_weak(utc.as_deref().unwrap_or(&e))
+                            .map_err(|err| format!("invalid expiration {}: {}", e, err))
+                    })
+                    .transpose()?,
+            })
+        } else {
+            let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+
+            let (access_key_id, secret_access_key) = match (lines.next(), lines.next()) {
+                (Some(access_key_id), Some(secret_access_key)) => {
+                    (access_key_id, secret_access_key)
+                }
+                _ => return Err("expected an access key id and a secret access key".into()),
+            };
+            let session_token = lines.next();
+
+            if lines.next().is_some() {
+                return Err(
+                    "expected at most an access key id, a secret access key and a session token"
+                        .into(),
+                );
+            }
+
+            Ok(Self {
+                access_key_id: access_key_id.to_string(),
+                secret_access_key: Zeroizing::new(secret_access_key.to_string()),
+                session_token: session_token.map(|t| Zeroizing::new(t.to_string())),
+                expiration: None,
+            })
+        }
+    }
+}
+
+/// Check that a profile name can be written as a section header and addressed by URI.
+pub fn validate_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() {
+        Err("profile name is empty".into())
+    } else if name.trim() != name {
+        Err(format!("profile name '{}' has leading or trailing whitespace", name).into())
+    } else if name.starts_with(';') || name.starts_with('#') {
+        Err(format!("profile name '{}' would be read as a comment", name).into())
+    } else if let Some(c) = name
+        .chars()
+        .find(|c| matches!(c, '[' | ']' | '/') || c.is_control())
+    {
+        Err(format!("profile name '{}' contains invalid character {:?}", name, c).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that a profile's name and credentials are well-formed before they're written to a file.
+pub fn validate(profile: &AwsProfile) -> Result<(), Error> {
+    validate_name(&profile.name)?;
+
+    let access_key_id = &profile.access_key_id;
+
+    if !(16..=128).contains(&access_key_id.len())
+        || !access_key_id
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return Err(format!(
+            "invalid access key id {}, expected 16 to 128 upper-case letters and digits",
+            profile.masked_access_key_id()
+        )
+        .into());
+    }
+
+    validate_secret("secret access key", &profile.secret_access_key)?;
+
+    match &profile.session_token {
+        Some(session_token) => validate_secret("session token", session_token)?,
+        None if profile.key_type() == KeyType::Temporary => {
+            return Err(format!(
+                "temporary access key id {} requires a session token",
+                profile.masked_access_key_id()
+            )
+            .into());
+        }
+        None => (),
+    }
+
+    if let Some(region) = &profile.region {
+        if region.is_empty()
+            || !region
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(format!("invalid region {}", region).into());
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_secret(what: &str, secret: &str) -> Result<(), Error> {
+    if secret.is_empty() {
+        Err(format!("{} is empty", what).into())
+    } else if !secret.chars().all(|c| c.is_ascii_graphic()) {
+        Err(format!("{} contains whitespace or non-printable characters", what).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// The properties defining the given profile, in the order they're written.
+fn properties(profile: &AwsProfile) -> Vec<(&'static str, Zeroizing<String>)> {
+    let mut properties = vec![
+        (
+            INI_ACCESS_KEY_ID_TAG,
+            Zeroizing::new(profile.access_key_id.clone()),
+        ),
+        (INI_SECRET_ACCESS_KEY_TAG, profile.secret_access_key.clone()),
+    ];
+
+    if let Some(session_token) = &profile.session_token {
+        properties.push((INI_SESSION_TOKEN_TAG, session_token.clone()));
+    }
+
+    if let Some(expiration) = profile.expiration {
+        properties.push((
+            INI_EXPIRATION_TAGS[0],
+            Zeroizing::new(humantime::format_rfc3339_seconds(expiration).to_string()),
+        ));
+    }
+
+    if let Some(region) = &profile.region {
+        properties.push((INI_REGION_TAG, Zeroizing::new(region.clone())));
+    }
+
+    properties
+}
+
+/// Add the section for a profile to the source of its file, replacing the properties of an existing section by the
+/// same name only if `force` is set.
+///
+/// New sections are appended, separated from the rest of the file by a blank line.
+pub fn upsert_section(
+    source: &str,
+    profile: &AwsProfile,
+    force: bool,
+) -> Result<Zeroizing<String>, Error> {
+    let mut document = Document::parse(source);
+
+    if document.section(&profile.name).is_some() {
+        if !force {
+            return Err(ProfileExists { uri: profile.uri() }.into());
+        }
+
+        document.clear_section(&profile.name);
+    }
+
+    for (key, value) in properties(profile) {
+        document.set(&profile.name, key, &value);
+    }
+
+    Ok(document.serialize())
+}
+
+/// The file a profile URI refers to and the profile name, where `/name` refers to `~/.aws/credentials` and
+/// `prefix/name` to the file in `~/.aws/credentials.d` with that prefix, or a new `prefix.ini` if there is none.
+///
+/// With `encrypt` set, a new file is named `prefix.gpg` rather than `prefix.ini`.
+pub async fn resolve_target(uri: &str, encrypt: bool) -> Result<(FileSource, String), Error> {
+    let (prefix, name) = uri
+        .split_once('/')
+        .ok_or_else(|| format!("expected prefix/name or /name, got '{}'", uri))?;
+
+    validate_name(name)?;
+
+    let aws_dir = utils::homedir().join(".aws");
+
+    if prefix.is_empty() {
+        return Ok((
+            FileSource::from_path(aws_dir.join("credentials")),
+            name.to_string(),
+        ));
+    }
+
+    validate_name(prefix).map_err(|e| format!("invalid prefix: {}", e))?;
+
+    let files = if aws_dir.is_dir() {
+        AwsCredentials::list_files()
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        vec![]
+    };
+
+    let mut matching: Vec<FileSource> = files
+        .into_iter()
+        .filter(|f| !f.is_root() && f.prefix().as_deref() == Some(prefix))
+        .collect();
+
+    match matching.len() {
+        0 => Ok((
+            FileSource::from_path(aws_dir.join("credentials.d").join(format!(
+                "{}.{}",
+                prefix,
+                if encrypt { "gpg" } else { "ini" }
+            ))),
+            name.to_string(),
+        )),
+        1 => Ok((matching.remove(0), name.to_string())),
+        _ => Err(format!(
+            "prefix '{}' is ambiguous, it is used by {}",
+            prefix,
+            matching
+                .iter()
+                .map(|f| f.home_path().display().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+/// Refuse files encrypted with anything but gpg, which would otherwise be edited as if they were plaintext.
+fn check_supported(file: &FileSource) -> Result<(), Error> {
+    if file.path.extension().and_then(OsStr::to_str) == Some("age") {
+        return Err(format!(
+            "{} is encrypted with age, which can't be edited, only gpg-encrypted files can",
+            file.home_path().display()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Read a credential file into memory, decrypting it if it is encrypted. Files which don't exist are empty.
+pub async fn read(file: &FileSource) -> Result<Zeroizing<String>, Error> {
+    check_supported(file)?;
+
+    if !file.path.exists() {
+        Ok(Zeroizing::new(String::new()))
+    } else if file.encrypted {
+        credentials_file::decrypt(&file.path).await
+    } else {
+        tokio::fs::read_to_string(&file.path)
+            .await
+            .map(Zeroizing::new)
+            .map_err(|e| format!("unable to read {}: {}", file.path.display(), e).into())
+    }
+}
+
+/// The key ids an encrypted file is encrypted to, as listed by gpg without decrypting the file.
+pub async fn recipients(file: &FileSource) -> Result<Vec<String>, Error> {
+    let output = Command::new("gpg")
+        .arg("--batch")
+        .arg("--list-only")
+        .arg("--list-packets")
+        .arg(&file.path)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("unable to run gpg: {}", e))?;
+
+    let mut recipients = Vec::new();
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if !line.starts_with(":pubkey enc packet:") {
+            continue;
+        }
+
+        if let Some(keyid) = line.split_once("keyid ").map(|(_, k)| k.trim()) {
+            if keyid.chars().all(|c| c == '0') {
+                return Err(format!(
+                    "{} is encrypted to a hidden recipient, recipients must be given explicitly",
+                    file.home_path().display()
+                )
+                .into());
+            }
+
+            recipients.push(keyid.to_string());
+        }
+    }
+
+    if recipients.is_empty() {
+        Err(format!(
+            "unable to find the recipients of {}, recipients must be given explicitly",
+            file.home_path().display()
+        )
+        .into())
+    } else {
+        Ok(recipients)
+    }
+}
+
+/// Atomically replace a credential file, keeping its mode, or creating it and its directory accessible only by the
+/// user.
+///
+/// Encrypted files are encrypted with gpg in memory, to the given recipients if any or otherwise to the recipients
+/// of the existing file, so that plaintext is never written to disk.
+pub async fn write(file: &FileSource, contents: &str, recipients: &[String]) -> Result<(), Error> {
+    check_supported(file)?;
+
+    let path = file.path.as_path();
+
+    let mode = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.permissions().mode() & 0o7777,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if let Some(parent) = path.parent() {
+                DirBuilder::new()
+                    .recursive(true)
+                    .mode(NEW_DIR_MODE)
+                    .create(parent)
+                    .map_err(|e| format!("unable to create {}: {}", parent.display(), e))?;
+            }
+
+            NEW_FILE_MODE
+        }
+        Err(e) => return Err(format!("unable to read {}: {}", path.display(), e).into()),
+    };
+
+    let ciphertext;
+    let bytes = if file.encrypted {
+        let recipients = if !recipients.is_empty() {
+            recipients.to_vec()
+        } else if path.exists() {
+            self::recipients(file).await?
+        } else {
+            return Err(format!(
+                "recipients must be given to create encrypted file {}",
+                file.home_path().display()
+            )
+            .into());
+        };
+
+        ciphertext = encrypt(path, contents, &recipients).await?;
+        ciphertext.as_slice()
+    } else if !recipients.is_empty() {
+        return Err(format!(
+            "{} is not encrypted, recipients can only be given for encrypted files",
+            file.home_path().display()
+        )
+        .into());
+    } else {
+        contents.as_bytes()
+    };
+
+    let mut atomic = AtomicFile::create(path, mode)
+        .map_err(|e| format!("unable to write {}: {}", path.display(), e))?;
+
+    atomic
+        .write_all(bytes)
+        .and_then(|_| atomic.commit())
+        .map_err(|e| format!("unable to write {}: {}", path.display(), e).into())
+}
+
+/// Encrypt plaintext with gpg, ASCII armored if the target is an `.asc` file.
+async fn encrypt(target: &Path, plaintext: &str, recipients: &[String]) -> Result<Vec<u8>, Error> {
+    let mut command = Command::new("gpg");
+
+    command
+        .arg("--batch")
+        .arg("--yes")
+        .arg("--quiet")
+        .arg("--encrypt");
+
+    if target.extension().and_then(|e| e.to_str()) == Some("asc") {
+        command.arg("--armor");
+    }
+
+    for recipient in recipients {
+        command.arg("--recipient").arg(recipient);
+    }
+
+    let mut child = command
+        .arg("--output")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("unable to run gpg: {}", e))?;
+
+    let (mut stdin, mut stdout, mut stderr) = (
+        child.stdin.take().unwrap(),
+        child.stdout.take().unwrap(),
+        child.stderr.take().unwrap(),
+    );
+
+    let (mut ciphertext, mut errors) = (Vec::new(), String::new());
+
+    // NOTE gpg is fed and drained concurrently, as it may block on a full stdout before reading all of stdin
+    let (written, _, _) = tokio::join!(
+        async move {
+            let result = stdin.write_all(plaintext.as_bytes()).await;
+            drop(stdin);
+            result
+        },
+        stdout.read_to_end(&mut ciphertext),
+        stderr.read_to_string(&mut errors),
+    );
+
+    let rc = child.wait().await?;
+
+    if !rc.success() || written.is_err() {
+        Err(format!(
+            "unable to encrypt file with gpg (rc={rc}): {p}\n{stderr}",
+            rc = rc.code().unwrap_or(-1),
+            p = target.display(),
+            stderr = errors
+                .lines()
+                .map(|line| format!("    {}", line))
+                .collect::<Vec<String>>()
+                .join("\n")
+        )
+        .into())
+    } else {
+        Ok(ciphertext)
+    }
+}
+
+/// Validate a profile and add it to its file, replacing an existing profile by the same name only if forced.
+pub async fn add_profile(profile: &AwsProfile, options: &EditOptions) -> Result<(), Error> {
+    validate(profile)?;
+
+    edit_file(&profile.file, options, |source| {
+        upsert_section(source, profile, options.force)
+    })
+    .await
+}
+
+/// Remove a profile from a file.
+pub async fn remove_profile(
+    file: &FileSource,
+    name: &str,
+    options: &EditOptions,
+) -> Result<(), Error> {
+    edit_file(file, options, |source| {
+        let mut document = Document::parse(source);
+
+        if document.remove_section(name) {
+            Ok(document.serialize())
+        } else {
+            Err(not_found(file, name))
+        }
+    })
+    .await
+}
+
+/// Rename a profile within its file, replacing an existing profile by the new name only if forced.
+pub async fn rename_profile(
+    file: &FileSource,
+    name: &str,
+    new_name: &str,
+    options: &EditOptions,
+) -> Result<(), Error> {
+    validate_name(new_name)?;
+
+    edit_file(file, options, |source| {
+        let mut document = Document::parse(source);
+
+        if document.section(name).is_none() {
+            return Err(not_found(file, name));
+        }
+
+        if name != new_name && document.section(new_name).is_some() {
+            if !options.force {
+                return Err(ProfileExists {
+                    uri: uri(file, new_name),
+                }
+                .into());
+            }
+
+            document.remove_section(new_name);
+        }
+
+        document.rename_section(name, new_name);
+
+        Ok(document.serialize())
+    })
+    .await
+}
+
+/// Read a file, edit it in memory and write it back, first backing it up if asked to.
+async fn edit_file<F>(file: &FileSource, options: &EditOptions, edit: F) -> Result<(), Error>
+where
+    F: FnOnce(&str) -> Result<Zeroizing<String>, Error>,
+{
+    let source = read(file).await?;
+    let result = edit(&source)?;
+
+    if options.backup && file.path.exists() {
+        backup(file)?;
+    }
+
+    write(file, &result, &options.recipients).await
+}
+
+/// Copy a file to its backup, `<file>.bak`, as is, so that backups of encrypted files stay encrypted.
+pub fn backup(file: &FileSource) -> Result<PathBuf, Error> {
+    let path = file.path.as_path();
+    let target = backup_path(path);
+
+    let copy = || -> io::Result<()> {
+        let mode = std::fs::metadata(path)?.permissions().mode() & 0o7777;
+        let contents = std::fs::read(path)?;
+
+        let mut atomic = AtomicFile::create(&target, mode)?;
+        atomic.write_all(&contents)?;
+        atomic.commit()
+    };
+
+    copy().map_err(|e| format!("unable to back up {}: {}", path.display(), e))?;
+
+    Ok(target)
+}
+
+/// The path a file is backed up to by `backup`.
+pub fn backup_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_os_string();
+    name.push(".");
+    name.push(BACKUP_EXTENSION);
+
+    PathBuf::from(name)
+}
+
+fn uri(file: &FileSource, name: &str) -> String {
+    match file.prefix() {
+        Some(prefix) => format!("{}/{}", prefix, name),
+        None => format!("/{}", name),
+    }
+}
+
+fn not_found(file: &FileSource, name: &str) -> Error {
+    ProfileNotFound {
+        name: uri(file, name),
+        suggestions: vec![],
+    }
+    .into()
+}
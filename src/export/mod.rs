@@ -0,0 +1,936 @@
+#[cfg(test)]
+mod tests;
+
+use crate::{utils, AtomicFile, AwsProfile, Error};
+
+use serde::{Serialize, Serializer};
+
+use zeroize::Zeroize;
+
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const ACCESS_KEY_ID_VAR: &str = "AWS_ACCESS_KEY_ID";
+pub const SECRET_ACCESS_KEY_VAR: &str = "AWS_SECRET_ACCESS_KEY";
+pub const SESSION_TOKEN_VAR: &str = "AWS_SESSION_TOKEN";
+
+/// The metadata variable naming the exported profile by its URI, e.g. `work/prod`.
+pub const PROFILE_VAR: &str = "AWS_ENV_PROFILE";
+/// The metadata variable naming the file the exported profile was read from.
+pub const SOURCE_VAR: &str = "AWS_ENV_SOURCE";
+/// The metadata variable holding when temporary credentials expire, as read by the AWS SDKs.
+pub const EXPIRATION_VAR: &str = "AWS_CREDENTIAL_EXPIRATION";
+
+/// The metadata variables and the fields of the profile they describe.
+const METADATA: [(&str, Field); 3] = [
+    (PROFILE_VAR, Field::Uri),
+    (SOURCE_VAR, Field::Source),
+    (EXPIRATION_VAR, Field::Expiration),
+];
+
+/// The environment variable pointing at the file GitHub Actions reads a step's exported variables from.
+pub const GITHUB_ENV_VAR: &str = "GITHUB_ENV";
+
+/// The name given to exported Kubernetes secrets unless another is configured.
+pub const DEFAULT_SECRET_NAME: &str = "aws-credentials";
+
+/// The names of all export formats, for use in command-line parsing.
+pub const EXPORT_FORMATS: &[&str] = &[
+    "shell",
+    "dotenv",
+    "docker-env",
+    "systemd",
+    "json",
+    "yaml",
+    "github-actions",
+    "gitlab-ci",
+    "k8s-secret",
+    "tfvars",
+    "tfvars-json",
+];
+
+/// YAML escape sequences for the C0 control characters, indexed by code point.
+const YAML_CONTROL_ESCAPES: [&str; 32] = [
+    "\\0", "\\x01", "\\x02", "\\x03", "\\x04", "\\x05", "\\x06", "\\a", "\\b", "\\t", "\\n", "\\v",
+    "\\f", "\\r", "\\x0e", "\\x0f", "\\x10", "\\x11", "\\x12", "\\x13", "\\x14", "\\x15", "\\x16",
+    "\\x17", "\\x18", "\\x19", "\\x1a", "\\e", "\\x1c", "\\x1d", "\\x1e", "\\x1f",
+];
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Output format for exporting a profile.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// POSIX shell `export` statements, suitable for `eval`.
+    #[default]
+    Shell,
+    /// A `.env` file as read by docker-compose and most dotenv libraries.
+    Dotenv,
+    /// A file for `docker run --env-file`, which takes values literally and has no quoting.
+    DockerEnv,
+    /// A file for a systemd unit's `EnvironmentFile=` setting.
+    Systemd,
+    /// A JSON object describing the profile and its credentials.
+    Json,
+    /// A YAML document describing the profile and its credentials.
+    Yaml,
+    /// GitHub Actions `::add-mask::` commands for the secrets, with the variables appended to `$GITHUB_ENV`.
+    GithubActions,
+    /// A GitLab CI dotenv report, which like docker's env files has no quoting. GitLab can't mask its variables.
+    GitlabCi,
+    /// A Kubernetes `v1/Secret` manifest holding the variables as base64 data.
+    K8sSecret,
+    /// A Terraform variable definitions file in HCL syntax.
+    Tfvars,
+    /// A Terraform variable definitions file in JSON syntax.
+    TfvarsJson,
+}
+
+impl ExportFormat {
+    /// The syntax used to write each variable assignment, if this is an environment variable format.
+    pub fn dialect(&self) -> Option<Dialect> {
+        match *self {
+            ExportFormat::Shell => Some(Dialect::Shell),
+            ExportFormat::Dotenv => Some(Dialect::Dotenv),
+            ExportFormat::DockerEnv => Some(Dialect::DockerEnv),
+            ExportFormat::Systemd => Some(Dialect::Systemd),
+            ExportFormat::GitlabCi => Some(Dialect::DockerEnv),
+            ExportFormat::Json
+            | ExportFormat::Yaml
+            | ExportFormat::GithubActions
+            | ExportFormat::K8sSecret
+            | ExportFormat::Tfvars
+            | ExportFormat::TfvarsJson => None,
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            ExportFormat::Shell => "shell",
+            ExportFormat::Dotenv => "dotenv",
+            ExportFormat::DockerEnv => "docker-env",
+            ExportFormat::Systemd => "systemd",
+            ExportFormat::Json => "json",
+            ExportFormat::Yaml => "yaml",
+            ExportFormat::GithubActions => "github-actions",
+            ExportFormat::GitlabCi => "gitlab-ci",
+            ExportFormat::K8sSecret => "k8s-secret",
+            ExportFormat::Tfvars => "tfvars",
+            ExportFormat::TfvarsJson => "tfvars-json",
+        })
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "shell" => Ok(ExportFormat::Shell),
+            "dotenv" => Ok(ExportFormat::Dotenv),
+            "docker-env" => Ok(ExportFormat::DockerEnv),
+            "systemd" => Ok(ExportFormat::Systemd),
+            "json" => Ok(ExportFormat::Json),
+            "yaml" => Ok(ExportFormat::Yaml),
+            "github-actions" => Ok(ExportFormat::GithubActions),
+            "gitlab-ci" => Ok(ExportFormat::GitlabCi),
+            "k8s-secret" => Ok(ExportFormat::K8sSecret),
+            "tfvars" => Ok(ExportFormat::Tfvars),
+            "tfvars-json" => Ok(ExportFormat::TfvarsJson),
+            _ => Err(format!("unknown format {}", s).into()),
+        }
+    }
+}
+
+/// Settings for the formats which name or place what they export.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExportOptions {
+    /// The name of the exported Kubernetes secret.
+    pub secret_name: String,
+    /// The namespace of the exported Kubernetes secret, or the current namespace of `kubectl` if absent.
+    pub namespace: Option<String>,
+    /// Whether environment variable formats also describe the profile in the [PROFILE_VAR], [SOURCE_VAR] and
+    /// [EXPIRATION_VAR] metadata variables. The other formats refuse it.
+    pub metadata: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            secret_name: DEFAULT_SECRET_NAME.to_string(),
+            namespace: None,
+            metadata: false,
+        }
+    }
+}
+
+/// The syntax of a single `NAME=value` environment variable assignment.
+///
+/// Values are escaped as they are written so that secrets are never copied into intermediate, unmanaged buffers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Dialect {
+    /// `export NAME=value`, single-quoting values containing anything but shell-safe characters.
+    Shell,
+    /// `NAME=value`, single-quoting values where possible and double-quoting with escapes otherwise.
+    Dotenv,
+    /// `NAME=value` with the value taken literally; values containing line breaks or NUL characters can't be
+    /// represented.
+    DockerEnv,
+    /// `NAME=value`, double-quoting values with backslash escapes where necessary.
+    Systemd,
+}
+
+impl Display for Dialect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Dialect::Shell => "shell",
+            Dialect::Dotenv => "dotenv",
+            Dialect::DockerEnv => "docker-env",
+            Dialect::Systemd => "systemd",
+        })
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "shell" => Ok(Dialect::Shell),
+            "dotenv" => Ok(Dialect::Dotenv),
+            "docker-env" => Ok(Dialect::DockerEnv),
+            "systemd" => Ok(Dialect::Systemd),
+            _ => Err(format!("unknown dialect {}", s).into()),
+        }
+    }
+}
+
+impl Dialect {
+    /// Write a single assignment, including the trailing newline.
+    pub fn write_assignment<W: Write>(
+        &self,
+        writer: &mut W,
+        name: &str,
+        value: &str,
+    ) -> io::Result<()> {
+        match *self {
+            Dialect::Shell => {
+                write!(writer, "export {}=", name)?;
+
+                if is_safe(value) {
+                    writer.write_all(value.as_bytes())?;
+                } else {
+                    writer.write_all(b"'")?;
+                    write_escaped(writer, value, |c| match c {
+                        '\'' => Some("'\\''"),
+                        _ => None,
+                    })?;
+                    writer.write_all(b"'")?;
+                }
+            }
+            Dialect::Dotenv => {
+                write!(writer, "{}=", name)?;
+
+                if is_safe(value) {
+                    writer.write_all(value.as_bytes())?;
+                } else if !value.contains(['\'', '\n', '\r']) {
+                    // single-quoted values are taken literally, without interpolation
+                    writer.write_all(b"'")?;
+                    writer.write_all(value.as_bytes())?;
+                    writer.write_all(b"'")?;
+                } else {
+                    writer.write_all(b"\"")?;
+                    write_escaped(writer, value, |c| match c {
+                        '\\' => Some("\\\\"),
+                        '"' => Some("\\\""),
+                        '$' => Some("\\$"),
+                        '\n' => Some("\\n"),
+                        '\r' => Some("\\r"),
+                        _ => None,
+                    })?;
+                    writer.write_all(b"\"")?;
+                }
+            }
+            Dialect::DockerEnv => {
+                check_representable(name, value, *self)?;
+
+                write!(writer, "{}=", name)?;
+                writer.write_all(value.as_bytes())?;
+            }
+            Dialect::Systemd => {
+                check_representable(name, value, *self)?;
+
+                write!(writer, "{}=", name)?;
+
+                if is_safe(value) {
+                    writer.write_all(value.as_bytes())?;
+                } else {
+                    writer.write_all(b"\"")?;
+                    write_escaped(writer, value, |c| match c {
+                        '\\' => Some("\\\\"),
+                        '"' => Some("\\\""),
+                        '`' => Some("\\`"),
+                        '$' => Some("\\$"),
+                        _ => None,
+                    })?;
+                    writer.write_all(b"\"")?;
+                }
+            }
+        }
+
+        writer.write_all(b"\n")
+    }
+}
+
+/// A property of a profile which a template can assign to a variable.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Field {
+    Name,
+    Uri,
+    AccessKeyId,
+    SecretAccessKey,
+    SessionToken,
+    Region,
+    Expiration,
+    /// The file the profile was read from, relative to the home directory.
+    Source,
+}
+
+impl Field {
+    /// The value of the field for the profile, if it has one.
+    pub fn value<'a>(&self, profile: &'a AwsProfile) -> Option<Cow<'a, str>> {
+        match *self {
+            Field::Name => Some(Cow::Borrowed(profile.name.as_str())),
+            Field::Uri => Some(Cow::Owned(profile.uri())),
+            Field::AccessKeyId => Some(Cow::Borrowed(profile.access_key_id.as_str())),
+            Field::SecretAccessKey => Some(Cow::Borrowed(profile.secret_access_key.as_str())),
+            Field::SessionToken => profile
+                .session_token
+                .as_ref()
+                .map(|t| Cow::Borrowed(t.as_str())),
+            Field::Region => profile.region.as_deref().map(Cow::Borrowed),
+            Field::Expiration => profile
+                .expiration
+                .map(|e| Cow::Owned(humantime::format_rfc3339_seconds(e).to_string())),
+            Field::Source => Some(Cow::Owned(
+                profile.file.home_path().to_string_lossy().into_owned(),
+            )),
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Field::Name => "name",
+            Field::Uri => "uri",
+            Field::AccessKeyId => "access_key_id",
+            Field::SecretAccessKey => "secret_access_key",
+            Field::SessionToken => "session_token",
+            Field::Region => "region",
+            Field::Expiration => "expiration",
+            Field::Source => "source",
+        })
+    }
+}
+
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "name" => Ok(Field::Name),
+            "uri" => Ok(Field::Uri),
+            "access_key_id" => Ok(Field::AccessKeyId),
+            "secret_access_key" => Ok(Field::SecretAccessKey),
+            "session_token" => Ok(Field::SessionToken),
+            "region" => Ok(Field::Region),
+            "expiration" => Ok(Field::Expiration),
+            "source" => Ok(Field::Source),
+            _ => Err(format!("unknown field {}", s).into()),
+        }
+    }
+}
+
+/// A user-defined export format, assigning profile fields to variables of the user's choosing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template {
+    pub name: String,
+    pub dialect: Dialect,
+    /// Pairs of variable names and the fields assigned to them, in output order.
+    pub variables: Vec<(String, Field)>,
+}
+
+impl Template {
+    pub fn new<S: Into<String>>(name: S, dialect: Dialect) -> Self {
+        Self {
+            name: name.into(),
+            dialect,
+            variables: Vec::new(),
+        }
+    }
+
+    /// Assign the field to the variable, which must be a valid environment variable name.
+    pub fn push<S: Into<String>>(&mut self, variable: S, field: Field) -> Result<(), Error> {
+        let variable = variable.into();
+
+        if !is_variable_name(variable.as_str()) {
+            return Err(format!("{} is not a valid environment variable name", variable).into());
+        }
+
+        self.variables.push((variable, field));
+
+        Ok(())
+    }
+
+    /// Add the [PROFILE_VAR], [SOURCE_VAR] and [EXPIRATION_VAR] metadata variables describing the profile.
+    pub fn push_metadata(&mut self) {
+        for (variable, field) in METADATA {
+            self.variables.push((variable.to_string(), field));
+        }
+    }
+
+    /// A copy of the template with every variable name prefixed by the upper-cased alias, e.g. `SRC_AWS_ACCESS_KEY_ID`
+    /// for the alias `src`.
+    pub fn prefixed(&self, alias: &str) -> Result<Template, Error> {
+        let prefix = alias.to_uppercase().replace('-', "_");
+        let mut template = Template::new(format!("{}:{}", alias, self.name), self.dialect);
+
+        for (variable, field) in self.variables.iter() {
+            template
+                .push(format!("{}_{}", prefix, variable), *field)
+                .map_err(|_| format!("{} can't be used as a variable prefix", alias))?;
+        }
+
+        Ok(template)
+    }
+}
+
+impl From<Dialect> for Template {
+    /// The standard AWS credential variables in the given dialect, as written by [write_env].
+    fn from(dialect: Dialect) -> Self {
+        Self {
+            name: dialect.to_string(),
+            dialect,
+            variables: vec![
+                (ACCESS_KEY_ID_VAR.to_string(), Field::AccessKeyId),
+                (SECRET_ACCESS_KEY_VAR.to_string(), Field::SecretAccessKey),
+                (SESSION_TOKEN_VAR.to_string(), Field::SessionToken),
+            ],
+        }
+    }
+}
+
+/// A structured view of a profile, borrowing the secrets straight from the profile's own zeroized buffers.
+#[derive(Serialize)]
+struct Record<'a> {
+    name: &'a str,
+    uri: String,
+    file: Cow<'a, str>,
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    session_token: Option<&'a str>,
+    region: Option<&'a str>,
+    expiration: Option<String>,
+}
+
+impl<'a> From<&'a AwsProfile> for Record<'a> {
+    fn from(profile: &'a AwsProfile) -> Self {
+        Self {
+            name: profile.name.as_str(),
+            uri: profile.uri(),
+            file: profile.file.path.to_string_lossy(),
+            access_key_id: profile.access_key_id.as_str(),
+            secret_access_key: profile.secret_access_key.as_str(),
+            session_token: profile.session_token.as_ref().map(|t| t.as_str()),
+            region: profile.region.as_deref(),
+            expiration: profile
+                .expiration
+                .map(|e| humantime::format_rfc3339_seconds(e).to_string()),
+        }
+    }
+}
+
+/// Write the profile in the given format.
+///
+/// Nothing is buffered: secrets are escaped and written piece by piece from the profile's own buffers, so callers
+/// should pass an unbuffered writer to avoid copies of secrets in memory that can't be zeroized.
+pub fn write_profile<W: Write>(
+    writer: &mut W,
+    profile: &AwsProfile,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> io::Result<()> {
+    if options.metadata && format.dialect().is_none() && format != ExportFormat::GithubActions {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("the {} format can't include the metadata variables", format),
+        ));
+    }
+
+    match format {
+        ExportFormat::Json => write_json(writer, profile),
+        ExportFormat::Yaml => write_yaml(writer, profile),
+        ExportFormat::K8sSecret => write_k8s_secret(
+            writer,
+            profile,
+            options.secret_name.as_str(),
+            options.namespace.as_deref(),
+        ),
+        ExportFormat::Tfvars => write_tfvars(writer, profile),
+        ExportFormat::TfvarsJson => write_tfvars_json(writer, profile),
+        ExportFormat::GithubActions => {
+            let path = std::env::var_os(GITHUB_ENV_VAR).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "${} is not set, not running in GitHub Actions?",
+                        GITHUB_ENV_VAR
+                    ),
+                )
+            })?;
+
+            let mut env = std::fs::OpenOptions::new().append(true).open(path)?;
+
+            write_github_actions(writer, &mut env, profile, options.metadata)
+        }
+        _ => {
+            let dialect = format.dialect().unwrap();
+
+            write_env(writer, profile, dialect)?;
+
+            if options.metadata {
+                let mut metadata = Template::new("metadata", dialect);
+                metadata.push_metadata();
+
+                write_template(writer, profile, &metadata)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// The standard AWS credential variables and their values for the profile.
+pub fn variables(profile: &AwsProfile) -> Vec<(&'static str, &str)> {
+    let mut variables = vec![
+        (ACCESS_KEY_ID_VAR, profile.access_key_id.as_str()),
+        (SECRET_ACCESS_KEY_VAR, profile.secret_access_key.as_str()),
+    ];
+
+    if let Some(session_token) = &profile.session_token {
+        variables.push((SESSION_TOKEN_VAR, session_token.as_str()));
+    }
+
+    variables
+}
+
+/// Write the standard AWS credential variables for the profile in the given dialect.
+pub fn write_env<W: Write>(
+    writer: &mut W,
+    profile: &AwsProfile,
+    dialect: Dialect,
+) -> io::Result<()> {
+    for (name, value) in variables(profile) {
+        dialect.write_assignment(writer, name, value)?;
+    }
+
+    Ok(())
+}
+
+/// Write GitHub Actions commands masking the secrets to `commands`, and the variables to the `env` file, followed by
+/// the metadata variables if asked to.
+///
+/// Each variable uses the multi-line delimiter syntax with a random delimiter, so that no value can inject further
+/// variables into the environment of later steps.
+pub fn write_github_actions<W: Write, E: Write>(
+    commands: &mut W,
+    env: &mut E,
+    profile: &AwsProfile,
+    metadata: bool,
+) -> io::Result<()> {
+    let delimiter = format!("ghadelimiter_{}", utils::random_token(16)?);
+
+    let mut variables: Vec<_> = variables(profile)
+        .into_iter()
+        .map(|(name, value)| (name, Cow::Borrowed(value)))
+        .collect();
+
+    if metadata {
+        variables.extend(
+            METADATA
+                .iter()
+                .filter_map(|(name, field)| Some((*name, field.value(profile)?))),
+        );
+    }
+
+    // NOTE check every value before writing any, so that a collision doesn't leave `$GITHUB_ENV` half-written
+    if let Some((name, _)) = variables
+        .iter()
+        .find(|(_, value)| value.contains(delimiter.as_str()))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the value of {} contains the heredoc delimiter", name),
+        ));
+    }
+
+    let secrets = std::iter::once(profile.secret_access_key.as_str())
+        .chain(profile.session_token.as_ref().map(|t| t.as_str()));
+
+    for secret in secrets {
+        // NOTE masks only apply to single lines, so mask every line of the value
+        for line in secret.lines().filter(|l| !l.is_empty()) {
+            writeln!(commands, "::add-mask::{}", line)?;
+        }
+    }
+
+    for (name, value) in variables {
+        writeln!(env, "{}<<{}", name, delimiter)?;
+        env.write_all(value.as_bytes())?;
+        writeln!(env, "\n{}", delimiter)?;
+    }
+
+    Ok(())
+}
+
+/// Write the variables of a user-defined template for the profile.
+///
+/// Variables assigned to fields the profile doesn't have, e.g. a session token for long-lived credentials, are left
+/// out.
+pub fn write_template<W: Write>(
+    writer: &mut W,
+    profile: &AwsProfile,
+    template: &Template,
+) -> io::Result<()> {
+    for (variable, field) in template.variables.iter() {
+        if let Some(value) = field.value(profile) {
+            template
+                .dialect
+                .write_assignment(writer, variable.as_str(), value.as_ref())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the variables of several profiles at once, each prefixed by its alias as with [Template::prefixed].
+///
+/// Either every profile is written or nothing is: all values are rendered into a sink first, so that a value the
+/// dialect can't represent fails the export before any variable reaches the writer.
+pub fn write_multi<W: Write>(
+    writer: &mut W,
+    profiles: &[(&str, &AwsProfile)],
+    template: &Template,
+) -> io::Result<()> {
+    let mut templates = Vec::with_capacity(profiles.len());
+
+    for (alias, _) in profiles {
+        if profiles
+            .iter()
+            .filter(|(a, _)| a.eq_ignore_ascii_case(alias))
+            .count()
+            > 1
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the alias {} is used more than once", alias),
+            ));
+        }
+
+        templates.push(
+            template
+                .prefixed(alias)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?,
+        );
+    }
+
+    for (template, (_, profile)) in templates.iter().zip(profiles) {
+        write_template(&mut io::sink(), profile, template)?;
+    }
+
+    for (template, (_, profile)) in templates.iter().zip(profiles) {
+        write_template(writer, profile, template)?;
+    }
+
+    Ok(())
+}
+
+/// Write a Kubernetes `v1/Secret` manifest with the standard AWS credential variables as its data.
+///
+/// The manifest can be applied with `kubectl apply -f -` and the secret used with `envFrom` in a pod's containers.
+pub fn write_k8s_secret<W: Write>(
+    writer: &mut W,
+    profile: &AwsProfile,
+    name: &str,
+    namespace: Option<&str>,
+) -> io::Result<()> {
+    if !is_dns_subdomain(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a valid Kubernetes secret name", name),
+        ));
+    }
+
+    if let Some(namespace) = namespace.filter(|n| !is_dns_label(n)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a valid Kubernetes namespace", namespace),
+        ));
+    }
+
+    writer.write_all(b"---\napiVersion: v1\nkind: Secret\nmetadata:\n")?;
+    writeln!(writer, "  name: {}", name)?;
+
+    if let Some(namespace) = namespace {
+        writeln!(writer, "  namespace: {}", namespace)?;
+    }
+
+    writer.write_all(b"type: Opaque\ndata:\n")?;
+
+    for (variable, value) in variables(profile) {
+        write!(writer, "  {}: ", variable)?;
+        write_base64(writer, value.as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Write a Terraform variable definitions file assigning the credentials to lowercase variables, e.g.
+/// `aws_access_key_id`.
+///
+/// Template sequences are escaped, so values are always taken literally.
+pub fn write_tfvars<W: Write>(writer: &mut W, profile: &AwsProfile) -> io::Result<()> {
+    for (name, value) in variables(profile) {
+        write!(writer, "{:<21} = \"", name.to_lowercase())?;
+
+        let mut chars = value.char_indices().peekable();
+        let mut start = 0;
+
+        while let Some((index, c)) = chars.next() {
+            let escape = match c {
+                '\\' => Cow::Borrowed("\\\\"),
+                '"' => Cow::Borrowed("\\\""),
+                '\n' => Cow::Borrowed("\\n"),
+                '\r' => Cow::Borrowed("\\r"),
+                '\t' => Cow::Borrowed("\\t"),
+                // NOTE `${` and `%{` begin template sequences, and are escaped by doubling the first character
+                '$' | '%' if matches!(chars.peek(), Some((_, '{'))) => {
+                    Cow::Borrowed(if c == '$' { "$$" } else { "%%" })
+                }
+                c if c.is_control() => Cow::Owned(format!("\\u{:04x}", c as u32)),
+                _ => continue,
+            };
+
+            writer.write_all(&value.as_bytes()[start..index])?;
+            writer.write_all(escape.as_bytes())?;
+            start = index + c.len_utf8();
+        }
+
+        writer.write_all(&value.as_bytes()[start..])?;
+        writer.write_all(b"\"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Write a Terraform variable definitions file in JSON syntax, with the same variables as [write_tfvars].
+pub fn write_tfvars_json<W: Write>(writer: &mut W, profile: &AwsProfile) -> io::Result<()> {
+    let mut serializer = serde_json::Serializer::pretty(&mut *writer);
+
+    serializer.collect_map(
+        variables(profile)
+            .into_iter()
+            .map(|(name, value)| (name.to_lowercase(), value)),
+    )?;
+    writer.write_all(b"\n")
+}
+
+fn write_json<W: Write>(writer: &mut W, profile: &AwsProfile) -> io::Result<()> {
+    // NOTE serde_json escapes and writes strings in pieces, without building the document in memory first
+    serde_json::to_writer_pretty(&mut *writer, &Record::from(profile))?;
+    writer.write_all(b"\n")
+}
+
+fn write_yaml<W: Write>(writer: &mut W, profile: &AwsProfile) -> io::Result<()> {
+    // NOTE serde_yaml builds an owned document tree before emitting it, so the YAML is written by hand instead
+    let record = Record::from(profile);
+
+    writer.write_all(b"---\n")?;
+
+    for (key, value) in [
+        ("name", Some(record.name)),
+        ("uri", Some(record.uri.as_str())),
+        ("file", Some(record.file.as_ref())),
+        ("access_key_id", Some(record.access_key_id)),
+        ("secret_access_key", Some(record.secret_access_key)),
+        ("session_token", record.session_token),
+        ("region", record.region),
+        ("expiration", record.expiration.as_deref()),
+    ] {
+        write!(writer, "{}: ", key)?;
+
+        match value {
+            Some(value) => write_yaml_string(writer, value)?,
+            None => writer.write_all(b"null")?,
+        }
+
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Write a double-quoted YAML scalar.
+fn write_yaml_string<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    write_escaped(writer, value, |c| match c {
+        '\\' => Some("\\\\"),
+        '"' => Some("\\\""),
+        '\u{7f}' => Some("\\x7f"),
+        c if (c as u32) < 0x20 => Some(YAML_CONTROL_ESCAPES[c as usize]),
+        _ => None,
+    })?;
+    writer.write_all(b"\"")
+}
+
+/// Create a file only readable by the current user to export credentials into, replacing it atomically on commit.
+///
+/// Existing files are only replaced if they are regular files owned by the current user and inaccessible to anyone
+/// else, so that exporting never follows a symbolic link or overwrites a file others may already be reading.
+pub fn create_private_file<P: AsRef<Path>>(path: P) -> io::Result<AtomicFile> {
+    let path = path.as_ref();
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            // SAFETY: getuid is always successful and has no side-effects
+            if metadata.uid() != unsafe { libc::getuid() } || metadata.mode() & 0o077 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "refusing to overwrite {} as it is accessible by other users",
+                        path.display()
+                    ),
+                ));
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+
+    AtomicFile::create(path, 0o600)
+}
+
+/// Write the bytes in standard, padded base64.
+///
+/// Encoded chunks go through a small buffer on the stack, which is zeroized once written.
+fn write_base64<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let mut buffer = [0u8; 64];
+    let mut result = Ok(());
+
+    for chunk in bytes.chunks(buffer.len() / 4 * 3) {
+        let mut length = 0;
+
+        for triple in chunk.chunks(3) {
+            let bits = triple
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+
+            for i in 0..4 {
+                buffer[length + i] = if i <= triple.len() {
+                    BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize]
+                } else {
+                    b'='
+                };
+            }
+
+            length += 4;
+        }
+
+        result = writer.write_all(&buffer[..length]);
+
+        if result.is_err() {
+            break;
+        }
+    }
+
+    buffer.zeroize();
+    result
+}
+
+/// Whether the name can be used as an environment variable in every dialect.
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether the name is a valid DNS subdomain, as required of most Kubernetes object names.
+fn is_dns_subdomain(name: &str) -> bool {
+    name.len() <= 253 && name.split('.').all(is_dns_label)
+}
+
+/// Whether the name is a valid DNS label, as required of Kubernetes namespaces.
+fn is_dns_label(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+/// Whether a value can be written without quoting in any dialect.
+///
+/// This covers the base64 alphabet used by AWS secrets and session tokens, so that the common case stays unquoted.
+fn is_safe(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./+=:@%,".contains(c))
+}
+
+/// Write a value, replacing characters for which `escape` returns a replacement.
+///
+/// Unescaped runs are written straight from the source string.
+fn write_escaped<W: Write, F: Fn(char) -> Option<&'static str>>(
+    writer: &mut W,
+    value: &str,
+    escape: F,
+) -> io::Result<()> {
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        if let Some(replacement) = escape(c) {
+            writer.write_all(&value.as_bytes()[start..index])?;
+            writer.write_all(replacement.as_bytes())?;
+            start = index + c.len_utf8();
+        }
+    }
+
+    writer.write_all(&value.as_bytes()[start..])
+}
+
+/// Refuse values with line breaks or NUL characters, which formats without quoting or escaping can't represent.
+fn check_representable(name: &str, value: &str, dialect: Dialect) -> io::Result<()> {
+    match value.chars().find(|c| matches!(c, '\n' | '\r' | '\0')) {
+        Some(c) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the value of {} contains the character {:?}, which can't be represented in the {} format",
+                name, c, dialect
+            ),
+        )),
+        None => Ok(()),
+    }
+}
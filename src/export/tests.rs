@@ -0,0 +1,479 @@
+use super::{
+    create_private_file, write_github_actions, write_multi, write_profile, Dialect, ExportFormat,
+    ExportOptions, Template,
+};
+use crate::loader::FileSource;
+use crate::AwsProfile;
+
+use std::fs::Permissions;
+use std::io::Write;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn assignment(dialect: Dialect, value: &str) -> String {
+    let mut output = Vec::new();
+    dialect
+        .write_assignment(&mut output, "NAME", value)
+        .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_profile_formats() {
+    let profile = AwsProfile::new(
+        "prod",
+        "AKIAEXAMPLE",
+        "abc/DEF+123=".into(),
+        Some("token".into()),
+        FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+    );
+
+    for (format, expected) in [
+        (
+            ExportFormat::Shell,
+            "export AWS_ACCESS_KEY_ID=AKIAEXAMPLE\nexport AWS_SECRET_ACCESS_KEY=abc/DEF+123=\nexport AWS_SESSION_TOKEN=token\n",
+        ),
+        (
+            ExportFormat::Dotenv,
+            "AWS_ACCESS_KEY_ID=AKIAEXAMPLE\nAWS_SECRET_ACCESS_KEY=abc/DEF+123=\nAWS_SESSION_TOKEN=token\n",
+        ),
+        (
+            ExportFormat::DockerEnv,
+            "AWS_ACCESS_KEY_ID=AKIAEXAMPLE\nAWS_SECRET_ACCESS_KEY=abc/DEF+123=\nAWS_SESSION_TOKEN=token\n",
+        ),
+        (
+            ExportFormat::Systemd,
+            "AWS_ACCESS_KEY_ID=AKIAEXAMPLE\nAWS_SECRET_ACCESS_KEY=abc/DEF+123=\nAWS_SESSION_TOKEN=token\n",
+        ),
+    ] {
+        let mut output = Vec::new();
+        write_profile(&mut output, &profile, format, &Default::default()).unwrap();
+
+        assert_eq!(expected, String::from_utf8(output).unwrap());
+        assert_eq!(format, format.to_string().parse().unwrap());
+    }
+}
+
+#[test]
+fn test_shell_escaping() {
+    assert_eq!("export NAME=''\n", assignment(Dialect::Shell, ""));
+    assert_eq!("export NAME='a b'\n", assignment(Dialect::Shell, "a b"));
+    assert_eq!("export NAME='$(x)'\n", assignment(Dialect::Shell, "$(x)"));
+    assert_eq!(
+        "export NAME='it'\\''s'\n",
+        assignment(Dialect::Shell, "it's")
+    );
+}
+
+#[test]
+fn test_dotenv_escaping() {
+    assert_eq!("NAME=''\n", assignment(Dialect::Dotenv, ""));
+    assert_eq!(
+        "NAME='a $b \"c\"'\n",
+        assignment(Dialect::Dotenv, "a $b \"c\"")
+    );
+    assert_eq!(
+        "NAME=\"it's \\$b\\\\\\n\"\n",
+        assignment(Dialect::Dotenv, "it's $b\\\n")
+    );
+}
+
+#[test]
+fn test_docker_env_escaping() {
+    // docker takes everything after the equals sign literally
+    assert_eq!(
+        "NAME=a 'b' \"c\"\n",
+        assignment(Dialect::DockerEnv, "a 'b' \"c\"")
+    );
+
+    let mut output = Vec::new();
+    assert!(Dialect::DockerEnv
+        .write_assignment(&mut output, "NAME", "a\nb")
+        .is_err());
+
+    let error = Dialect::DockerEnv
+        .write_assignment(&mut output, "NAME", "a\0b")
+        .unwrap_err();
+    assert_eq!(
+        "the value of NAME contains the character '\\0', which can't be represented in the docker-env format",
+        error.to_string()
+    );
+}
+
+#[test]
+fn test_systemd_escaping() {
+    assert_eq!("NAME=\"a b\"\n", assignment(Dialect::Systemd, "a b"));
+    assert_eq!(
+        "NAME=\"\\$a\\\\\\\"\\`\"\n",
+        assignment(Dialect::Systemd, "$a\\\"`")
+    );
+
+    let mut output = Vec::new();
+    assert!(Dialect::Systemd
+        .write_assignment(&mut output, "NAME", "a\rb")
+        .is_err());
+}
+
+#[test]
+fn test_structured_formats() {
+    let mut profile = AwsProfile::new(
+        "prod",
+        "ASIAEXAMPLE",
+        "abc/DEF+123=".into(),
+        Some("to\"ken\n".into()),
+        FileSource::from_path(
+            crate::utils::homedir()
+                .join(".aws")
+                .join("credentials.d")
+                .join("work.ini"),
+        ),
+    );
+
+    profile.region = Some("eu-west-1".into());
+    profile.expiration = Some(UNIX_EPOCH + Duration::from_secs(1_800_000_000));
+
+    let file = profile.file.path.display().to_string();
+
+    let mut output = Vec::new();
+    write_profile(
+        &mut output,
+        &profile,
+        ExportFormat::Json,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+    assert_eq!("prod", json["name"]);
+    assert_eq!("work/prod", json["uri"]);
+    assert_eq!(file.as_str(), json["file"]);
+    assert_eq!("ASIAEXAMPLE", json["access_key_id"]);
+    assert_eq!("abc/DEF+123=", json["secret_access_key"]);
+    assert_eq!("to\"ken\n", json["session_token"]);
+    assert_eq!("eu-west-1", json["region"]);
+    assert_eq!("2027-01-15T08:00:00Z", json["expiration"]);
+
+    let mut output = Vec::new();
+    write_profile(
+        &mut output,
+        &profile,
+        ExportFormat::Yaml,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let yaml: serde_yaml::Value = serde_yaml::from_slice(&output).unwrap();
+
+    assert_eq!("prod", yaml["name"]);
+    assert_eq!("work/prod", yaml["uri"]);
+    assert_eq!(file.as_str(), yaml["file"]);
+    assert_eq!("ASIAEXAMPLE", yaml["access_key_id"]);
+    assert_eq!("abc/DEF+123=", yaml["secret_access_key"]);
+    assert_eq!("to\"ken\n", yaml["session_token"]);
+    assert_eq!("eu-west-1", yaml["region"]);
+    assert_eq!("2027-01-15T08:00:00Z", yaml["expiration"]);
+
+    // optional fields are null when absent
+    profile.session_token = None;
+    profile.region = None;
+    profile.expiration = None;
+
+    let mut output = Vec::new();
+    write_profile(
+        &mut output,
+        &profile,
+        ExportFormat::Yaml,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let yaml: serde_yaml::Value = serde_yaml::from_slice(&output).unwrap();
+
+    assert!(yaml["session_token"].is_null());
+    assert!(yaml["region"].is_null());
+    assert!(yaml["expiration"].is_null());
+}
+
+#[test]
+fn test_private_file() {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+
+    let dir = std::env::temp_dir().join(format!("aws-env-test-private-{}", nonce));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let target = dir.join("credentials.env");
+
+    // new files are created only readable by the current user
+    let mut file = create_private_file(&target).unwrap();
+    file.write_all(b"secret").unwrap();
+    file.commit().unwrap();
+
+    assert_eq!(
+        0o600,
+        std::fs::metadata(&target).unwrap().permissions().mode() & 0o777
+    );
+
+    // private files may be replaced
+    create_private_file(&target).unwrap().commit().unwrap();
+
+    // files readable by others may not
+    std::fs::set_permissions(&target, Permissions::from_mode(0o644)).unwrap();
+    assert!(create_private_file(&target).is_err());
+
+    // nor may symbolic links, even to private files
+    std::fs::set_permissions(&target, Permissions::from_mode(0o600)).unwrap();
+    symlink(&target, dir.join("link")).unwrap();
+    assert!(create_private_file(dir.join("link")).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_ci_formats() {
+    let profile = AwsProfile::new(
+        "prod",
+        "ASIAEXAMPLE",
+        "abc/DEF+123=".into(),
+        Some("multi\nline".into()),
+        FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+    );
+
+    let (mut commands, mut env) = (Vec::new(), Vec::new());
+    write_github_actions(&mut commands, &mut env, &profile, false).unwrap();
+
+    assert_eq!(
+        "::add-mask::abc/DEF+123=\n::add-mask::multi\n::add-mask::line\n",
+        String::from_utf8(commands).unwrap()
+    );
+
+    let env = String::from_utf8(env).unwrap();
+    let delimiter = env.lines().next().unwrap().split("<<").nth(1).unwrap();
+
+    assert!(delimiter.starts_with("ghadelimiter_"));
+    assert_eq!(
+        format!(
+            "AWS_ACCESS_KEY_ID<<{d}\nASIAEXAMPLE\n{d}\nAWS_SECRET_ACCESS_KEY<<{d}\nabc/DEF+123=\n{d}\nAWS_SESSION_TOKEN<<{d}\nmulti\nline\n{d}\n",
+            d = delimiter
+        ),
+        env
+    );
+
+    // GitLab's dotenv reports have no quoting, and can't hold multiple lines
+    let mut output = Vec::new();
+    assert!(write_profile(
+        &mut output,
+        &profile,
+        ExportFormat::GitlabCi,
+        &Default::default()
+    )
+    .is_err());
+}
+
+#[test]
+fn test_k8s_secret() {
+    let profile = AwsProfile::new(
+        "prod",
+        "ASIAEXAMPLE",
+        "abc/DEF+123=".into(),
+        Some("to".into()),
+        FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+    );
+
+    let mut options = ExportOptions::default();
+
+    let mut output = Vec::new();
+    write_profile(&mut output, &profile, ExportFormat::K8sSecret, &options).unwrap();
+
+    assert_eq!(
+        "---\napiVersion: v1\nkind: Secret\nmetadata:\n  name: aws-credentials\ntype: Opaque\ndata:\n  AWS_ACCESS_KEY_ID: QVNJQUVYQU1QTEU=\n  AWS_SECRET_ACCESS_KEY: YWJjL0RFRisxMjM9\n  AWS_SESSION_TOKEN: dG8=\n",
+        String::from_utf8(output).unwrap()
+    );
+
+    options.secret_name = "prod.credentials".into();
+    options.namespace = Some("kube-system".into());
+
+    let mut output = Vec::new();
+    write_profile(&mut output, &profile, ExportFormat::K8sSecret, &options).unwrap();
+
+    let yaml: serde_yaml::Value = serde_yaml::from_slice(&output).unwrap();
+
+    assert_eq!("prod.credentials", yaml["metadata"]["name"]);
+    assert_eq!("kube-system", yaml["metadata"]["namespace"]);
+
+    // names are written unquoted, so must be valid
+    for (name, namespace) in [
+        ("Prod", None),
+        ("prod: x", None),
+        ("-prod", None),
+        ("prod", Some("a.b")),
+    ] {
+        options.secret_name = name.into();
+        options.namespace = namespace.map(String::from);
+
+        assert!(
+            write_profile(&mut Vec::new(), &profile, ExportFormat::K8sSecret, &options).is_err()
+        );
+    }
+}
+
+#[test]
+fn test_tfvars_formats() {
+    let profile = AwsProfile::new(
+        "prod",
+        "ASIAEXAMPLE",
+        "a\"b\\c${d}%{e}$f\n".into(),
+        None,
+        FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+    );
+
+    let mut output = Vec::new();
+    write_profile(
+        &mut output,
+        &profile,
+        ExportFormat::Tfvars,
+        &Default::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        "aws_access_key_id     = \"ASIAEXAMPLE\"\naws_secret_access_key = \"a\\\"b\\\\c$${d}%%{e}$f\\n\"\n",
+        String::from_utf8(output).unwrap()
+    );
+
+    let mut output = Vec::new();
+    write_profile(
+        &mut output,
+        &profile,
+        ExportFormat::TfvarsJson,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+    assert_eq!("ASIAEXAMPLE", json["aws_access_key_id"]);
+    assert_eq!("a\"b\\c${d}%{e}$f\n", json["aws_secret_access_key"]);
+    assert!(json.get("aws_session_token").is_none());
+}
+
+#[test]
+fn test_multi() {
+    let source = AwsProfile::new(
+        "prod",
+        "AKIASOURCE",
+        "source".into(),
+        None,
+        FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+    );
+
+    let destination = AwsProfile::new(
+        "prod",
+        "ASIADESTINATION",
+        "destination".into(),
+        Some("multi\nline".into()),
+        FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+    );
+
+    let mut output = Vec::new();
+    write_multi(
+        &mut output,
+        &[("src", &source), ("dst-2", &destination)],
+        &Template::from(Dialect::Shell),
+    )
+    .unwrap();
+
+    assert_eq!(
+        "export SRC_AWS_ACCESS_KEY_ID=AKIASOURCE\nexport SRC_AWS_SECRET_ACCESS_KEY=source\nexport DST_2_AWS_ACCESS_KEY_ID=ASIADESTINATION\nexport DST_2_AWS_SECRET_ACCESS_KEY=destination\nexport DST_2_AWS_SESSION_TOKEN='multi\nline'\n",
+        String::from_utf8(output).unwrap()
+    );
+
+    // nothing is written if any profile can't be represented
+    let mut output = Vec::new();
+    assert!(write_multi(
+        &mut output,
+        &[("src", &source), ("dst", &destination)],
+        &Template::from(Dialect::DockerEnv),
+    )
+    .is_err());
+    assert!(output.is_empty());
+
+    // nor if the aliases are unusable
+    for aliases in [["src", "SRC"], ["src", "$(x)"]] {
+        assert!(write_multi(
+            &mut output,
+            &[(aliases[0], &source), (aliases[1], &destination)],
+            &Template::from(Dialect::Shell),
+        )
+        .is_err());
+        assert!(output.is_empty());
+    }
+}
+
+#[test]
+fn test_metadata() {
+    let mut profile = AwsProfile::new(
+        "prod",
+        "AKIAEXAMPLE",
+        "secret".into(),
+        None,
+        FileSource::from_path(
+            crate::utils::homedir()
+                .join(".aws")
+                .join("credentials.d")
+                .join("work.asc"),
+        ),
+    );
+
+    let options = ExportOptions {
+        metadata: true,
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    write_profile(&mut output, &profile, ExportFormat::Dotenv, &options).unwrap();
+
+    // the expiration is left out for long-lived credentials, and the tilde is quoted to stop it being expanded
+    assert_eq!(
+        "AWS_ACCESS_KEY_ID=AKIAEXAMPLE\nAWS_SECRET_ACCESS_KEY=secret\nAWS_ENV_PROFILE=work/prod\nAWS_ENV_SOURCE='~/.aws/credentials.d/work.asc'\n",
+        String::from_utf8(output).unwrap()
+    );
+
+    profile.expiration = Some(UNIX_EPOCH + Duration::from_secs(1_800_000_000));
+
+    let mut template = Template::from(Dialect::Shell);
+    template.push_metadata();
+
+    let mut output = Vec::new();
+    write_multi(&mut output, &[("src", &profile)], &template).unwrap();
+
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .ends_with("export SRC_AWS_ENV_PROFILE=work/prod\nexport SRC_AWS_ENV_SOURCE='~/.aws/credentials.d/work.asc'\nexport SRC_AWS_CREDENTIAL_EXPIRATION=2027-01-15T08:00:00Z\n"));
+
+    // GitHub Actions gets them in its environment file like the credentials
+    let (mut commands, mut env) = (Vec::new(), Vec::new());
+    write_github_actions(&mut commands, &mut env, &profile, true).unwrap();
+
+    let env = String::from_utf8(env).unwrap();
+    let delimiter = env.lines().next().unwrap().split("<<").nth(1).unwrap();
+
+    assert!(env.ends_with(&format!(
+        "AWS_ENV_PROFILE<<{d}\nwork/prod\n{d}\nAWS_ENV_SOURCE<<{d}\n~/.aws/credentials.d/work.asc\n{d}\nAWS_CREDENTIAL_EXPIRATION<<{d}\n2027-01-15T08:00:00Z\n{d}\n",
+        d = delimiter
+    )));
+
+    // while the structured formats refuse them rather than leaving them out
+    for format in [
+        ExportFormat::Json,
+        ExportFormat::K8sSecret,
+        ExportFormat::Tfvars,
+    ] {
+        let mut output = Vec::new();
+        assert!(write_profile(&mut output, &profile, format, &options).is_err());
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+#[cfg(test)]
+mod tests;
+
+use crate::utils::http::{self, json, status, text, Handler};
+use crate::{utils, AwsProfile, Error};
+
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The header carrying the requested lifetime of a session token, in seconds.
+pub const TOKEN_TTL_HEADER: &str = "x-aws-ec2-metadata-token-ttl-seconds";
+/// The header carrying a session token on metadata requests.
+pub const TOKEN_HEADER: &str = "x-aws-ec2-metadata-token";
+
+/// The longest lifetime a session token may be requested for, six hours.
+const MAX_TOKEN_TTL: u64 = 21600;
+
+const TOKEN_PATH: &str = "/latest/api/token";
+const CREDENTIALS_PATH: &str = "/latest/meta-data/iam/security-credentials";
+
+/// Instance role credentials in the format returned by the EC2 instance metadata service.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Credentials<'a> {
+    code: &'a str,
+    last_updated: String,
+    #[serde(rename = "Type")]
+    kind: &'a str,
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    token: &'a str,
+    expiration: String,
+}
+
+/// An emulation of the EC2 instance metadata service (IMDSv2) serving the credentials of a single profile.
+///
+/// Clients must first obtain a session token via `PUT /latest/api/token`, then present it on every request to
+/// `/latest/meta-data/iam/security-credentials/`, exactly like they would on an EC2 instance. IMDSv1 requests without a
+/// token are rejected.
+pub struct Imds {
+    profile: AwsProfile,
+    role: String,
+    lifetime: Duration,
+    tokens: Mutex<HashMap<String, Instant>>,
+}
+
+impl Imds {
+    /// Create a service for the given profile, reported as the instance role `role`.
+    ///
+    /// The `lifetime` is the expiration advertised to clients, after which they will request credentials again.
+    pub fn new<S: Into<String>>(profile: AwsProfile, role: S, lifetime: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            profile,
+            role: role.into(),
+            lifetime,
+            tokens: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Serve requests on the given listener until the `shutdown` future completes.
+    pub async fn serve<F: Future<Output = ()>>(
+        self: Arc<Self>,
+        listener: TcpListener,
+        shutdown: F,
+    ) -> Result<(), Error> {
+        http::serve(self, listener, shutdown).await
+    }
+
+    fn issue_token(&self, request: &Request<Body>) -> Response<Body> {
+        // like the real service, refuse tokens to requests that have passed through a proxy
+        if request.headers().contains_key("x-forwarded-for") {
+            return status(StatusCode::FORBIDDEN);
+        }
+
+        let ttl = match request
+            .headers()
+            .get(TOKEN_TTL_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            Some(ttl) if (1..=MAX_TOKEN_TTL).contains(&ttl) => ttl,
+            _ => return status(StatusCode::BAD_REQUEST),
+        };
+
+        let token = match utils::random_token(32) {
+            Ok(token) => token,
+            Err(e) => {
+                log::error!("Unable to generate IMDS session token: {}", e);
+                return status(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+        let mut tokens = self.tokens.lock().unwrap();
+        let now = Instant::now();
+
+        tokens.retain(|_, expiry| *expiry > now);
+        tokens.insert(token.clone(), now + Duration::from_secs(ttl));
+
+        Response::builder()
+            .header(CONTENT_TYPE, "text/plain")
+            .header(TOKEN_TTL_HEADER, ttl)
+            .body(Body::from(token))
+            .unwrap()
+    }
+
+    fn is_authorized(&self, request: &Request<Body>) -> bool {
+        match request
+            .headers()
+            .get(TOKEN_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(token) => matches!(
+                self.tokens.lock().unwrap().get(token),
+                Some(expiry) if *expiry > Instant::now()
+            ),
+            None => false,
+        }
+    }
+
+    fn credentials(&self) -> Response<Body> {
+        let now = SystemTime::now();
+
+        let credentials = Credentials {
+            code: "Success",
+            last_updated: humantime::format_rfc3339_seconds(now).to_string(),
+            kind: "AWS-HMAC",
+            access_key_id: self.profile.access_key_id.as_str(),
+            secret_access_key: self.profile.secret_access_key.as_str(),
+            token: self
+                .profile
+                .session_token
+                .as_ref()
+                .map(|t| t.as_str())
+                .unwrap_or_default(),
+            expiration: humantime::format_rfc3339_seconds(now + self.lifetime).to_string(),
+        };
+
+        json(&credentials, "text/plain")
+    }
+}
+
+impl Handler for Imds {
+    fn respond(&self, request: &Request<Body>) -> Response<Body> {
+        let path = request.uri().path().trim_end_matches('/');
+
+        if path == TOKEN_PATH {
+            return match *request.method() {
+                Method::PUT => self.issue_token(request),
+                _ => status(StatusCode::METHOD_NOT_ALLOWED),
+            };
+        }
+
+        if request.method() != Method::GET {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        if !self.is_authorized(request) {
+            return status(StatusCode::UNAUTHORIZED);
+        }
+
+        if path == CREDENTIALS_PATH {
+            text(self.role.as_str())
+        } else if path == format!("{}/{}", CREDENTIALS_PATH, self.role) {
+            self.credentials()
+        } else {
+            status(StatusCode::NOT_FOUND)
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::{Imds, TOKEN_HEADER, TOKEN_TTL_HEADER};
+use crate::loader::FileSource;
+use crate::AwsProfile;
+
+use hyper::body::to_bytes;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, StatusCode};
+
+use std::net::{SocketAddr, TcpListener};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+fn profile() -> AwsProfile {
+    AwsProfile::new(
+        "prod",
+        "ASIAEXAMPLE",
+        "secret".into(),
+        Some("token".into()),
+        FileSource::from_path(crate::utils::homedir().join(".aws").join("credentials")),
+    )
+}
+
+async fn request(
+    client: &Client<HttpConnector>,
+    method: Method,
+    address: SocketAddr,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> (StatusCode, String) {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(format!("http://{}{}", address, path));
+
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+
+    let response = client
+        .request(builder.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body()).await.unwrap();
+
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_token_handshake() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+
+    let server = tokio::spawn(
+        Imds::new(profile(), "aws-env", Duration::from_secs(3600)).serve(listener, async {
+            stopped.await.ok();
+        }),
+    );
+
+    let client = Client::new();
+    let roles = "/latest/meta-data/iam/security-credentials/";
+
+    // IMDSv1 requests without a token are refused
+    let (status, _) = request(&client, Method::GET, address, roles, &[]).await;
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+
+    // tokens require a valid ttl
+    for ttl in [None, Some("0"), Some("21601"), Some("abc")] {
+        let headers = ttl.map(|t| vec![(TOKEN_TTL_HEADER, t)]).unwrap_or_default();
+        let (status, _) =
+            request(&client, Method::PUT, address, "/latest/api/token", &headers).await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+    }
+
+    // tokens are refused to proxied requests
+    let (status, _) = request(
+        &client,
+        Method::PUT,
+        address,
+        "/latest/api/token",
+        &[(TOKEN_TTL_HEADER, "60"), ("X-Forwarded-For", "10.0.0.1")],
+    )
+    .await;
+    assert_eq!(StatusCode::FORBIDDEN, status);
+
+    let (status, token) = request(
+        &client,
+        Method::PUT,
+        address,
+        "/latest/api/token",
+        &[(TOKEN_TTL_HEADER, "60")],
+    )
+    .await;
+    assert_eq!(StatusCode::OK, status);
+    assert!(!token.is_empty());
+
+    // unknown tokens are refused
+    let (status, _) = request(
+        &client,
+        Method::GET,
+        address,
+        roles,
+        &[(TOKEN_HEADER, "nope")],
+    )
+    .await;
+    assert_eq!(StatusCode::UNAUTHORIZED, status);
+
+    let (status, body) = request(
+        &client,
+        Method::GET,
+        address,
+        roles,
+        &[(TOKEN_HEADER, &token)],
+    )
+    .await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("aws-env", body);
+
+    let (status, body) = request(
+        &client,
+        Method::GET,
+        address,
+        "/latest/meta-data/iam/security-credentials/aws-env",
+        &[(TOKEN_HEADER, &token)],
+    )
+    .await;
+    assert_eq!(StatusCode::OK, status);
+
+    let credentials: serde_json::Value = serde_json::from_str(body.as_str()).unwrap();
+
+    assert_eq!("Success", credentials["Code"]);
+    assert_eq!("AWS-HMAC", credentials["Type"]);
+    assert_eq!("ASIAEXAMPLE", credentials["AccessKeyId"]);
+    assert_eq!("secret", credentials["SecretAccessKey"]);
+    assert_eq!("token", credentials["Token"]);
+    assert!(humantime::parse_rfc3339(credentials["Expiration"].as_str().unwrap()).is_ok());
+    assert!(humantime::parse_rfc3339(credentials["LastUpdated"].as_str().unwrap()).is_ok());
+
+    let (status, _) = request(
+        &client,
+        Method::GET,
+        address,
+        "/latest/meta-data/iam/security-credentials/other-role",
+        &[(TOKEN_HEADER, &token)],
+    )
+    .await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
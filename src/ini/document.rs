@@ -0,0 +1,476 @@
+#[cfg(test)]
+mod tests;
+
+use std::ops::Range;
+
+use zeroize::Zeroizing;
+
+/// What a line of an INI document holds, with the spans of its parts within the line's text.
+///
+/// Lines are told apart the way the AWS CLI reads its shared configuration files, see `Classifier`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum LineKind {
+    Blank,
+    /// A line starting with `;` or `#`, ignoring leading whitespace.
+    Comment,
+    /// A `[name]` section header, with the span of the trimmed name.
+    Section(Range<usize>),
+    /// A `key = value` or `key: value` property, with the spans of the trimmed key and value.
+    Property(Range<usize>, Range<usize>),
+    /// A line indented deeper than the property before it, which continues its value, with the span of the trimmed
+    /// text.
+    Continuation(Range<usize>),
+    /// Any other line, such as a header missing its `]` or a key without a value.
+    Malformed,
+}
+
+/// Classifies the lines of a document in order, as whether a line continues a property depends on the lines before it.
+///
+/// These are the rules of the AWS CLI, which reads its files with Python's `configparser`:
+///
+/// - comments take up whole lines, so a `;` or `#` after a value is part of the value;
+/// - anything after the `]` closing a section header is ignored, such as a comment;
+/// - the first `=` or `:` of a line separates the key from the value;
+/// - a line indented deeper than the property before it continues that property's value, across blank lines and
+///   comments, which is how `s3 =` blocks nest properties within a property.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Classifier {
+    /// The indentation of the property whose value deeper indented lines continue, if any.
+    property_indent: Option<usize>,
+}
+
+impl Classifier {
+    pub fn classify(&mut self, text: &str) -> LineKind {
+        let trimmed = text.trim();
+        let start = text.len() - text.trim_start().len();
+        let indent = text[..start].chars().count();
+
+        let kind = if trimmed.is_empty() {
+            LineKind::Blank
+        } else if trimmed.starts_with(';') || trimmed.starts_with('#') {
+            LineKind::Comment
+        } else if self.property_indent.is_some_and(|i| indent > i) {
+            LineKind::Continuation(start..start + trimmed.len())
+        } else {
+            Self::header(text, start)
+                .or_else(|| Self::property(text, start))
+                .unwrap_or(LineKind::Malformed)
+        };
+
+        match kind {
+            LineKind::Property(..) => self.property_indent = Some(indent),
+            LineKind::Section(_) | LineKind::Malformed => self.property_indent = None,
+            _ => (),
+        }
+
+        kind
+    }
+
+    /// A section header, whose name runs up to the last `]` of the line.
+    fn header(text: &str, start: usize) -> Option<LineKind> {
+        let close = text.rfind(']').filter(|_| text[start..].starts_with('['))?;
+        let name = trimmed_span(text, start + 1..close);
+
+        (!name.is_empty()).then_some(LineKind::Section(name))
+    }
+
+    fn property(text: &str, start: usize) -> Option<LineKind> {
+        let separator = text.find(['=', ':'])?;
+        let key = trimmed_span(text, start..separator);
+
+        (!key.is_empty())
+            .then(|| LineKind::Property(key, trimmed_span(text, separator + 1..text.len())))
+    }
+}
+
+/// Split a source into lines and their endings, which are either `\n` or `\r\n`.
+pub(crate) fn split_lines(source: &str) -> impl Iterator<Item = (&str, &'static str)> {
+    let mut rest = source;
+
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let (line, ending, next) = match rest.find('\n') {
+            Some(i) if rest[..i].ends_with('\r') => (&rest[..i - 1], "\r\n", &rest[i + 1..]),
+            Some(i) => (&rest[..i], "\n", &rest[i + 1..]),
+            None => (rest, "", ""),
+        };
+
+        rest = next;
+        Some((line, ending))
+    })
+}
+
+/// Whether two keys are the same, as the AWS CLI ignores the case of keys.
+fn same_key(a: &str, b: &str) -> bool {
+    a == b || a.to_lowercase() == b.to_lowercase()
+}
+
+/// A line of an INI document, keeping its exact text and line ending.
+///
+/// As documents tend to hold secrets, the text is securely erased once dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct Line {
+    text: Zeroizing<String>,
+    ending: &'static str,
+    kind: LineKind,
+}
+
+impl Line {
+    /// A line which is yet to be classified, see `Document::classify`.
+    fn new(text: &str, ending: &'static str) -> Self {
+        Self {
+            kind: LineKind::Blank,
+            text: Zeroizing::new(text.to_string()),
+            ending,
+        }
+    }
+
+    /// The line ending, which is empty for the last line of a document without a trailing newline.
+    #[cfg(test)]
+    pub fn ending(&self) -> &str {
+        self.ending
+    }
+
+    #[cfg(test)]
+    pub fn kind(&self) -> &LineKind {
+        &self.kind
+    }
+
+    /// The name of the section the line opens, if it is a section header.
+    pub fn section(&self) -> Option<&str> {
+        match &self.kind {
+            LineKind::Section(name) => Some(&self.text[name.clone()]),
+            _ => None,
+        }
+    }
+
+    /// The key of a property, as written.
+    pub fn key(&self) -> Option<&str> {
+        match &self.kind {
+            LineKind::Property(key, _) => Some(&self.text[key.clone()]),
+            _ => None,
+        }
+    }
+
+    /// The value of a property.
+    #[cfg(test)]
+    pub fn value(&self) -> Option<&str> {
+        match &self.kind {
+            LineKind::Property(_, value) => Some(&self.text[value.clone()]),
+            _ => None,
+        }
+    }
+
+    /// Whether the line is a property with the given key, ignoring case.
+    fn has_key(&self, key: &str) -> bool {
+        self.key().is_some_and(|k| same_key(k, key))
+    }
+
+    /// Whether the line holds part of a section's content, as opposed to blank lines and comments.
+    fn is_content(&self) -> bool {
+        !matches!(self.kind, LineKind::Blank | LineKind::Comment)
+    }
+
+    /// Replace a span of the text, which leaves the line to be classified anew.
+    fn splice(&mut self, span: Range<usize>, replacement: &str) {
+        let mut text = Zeroizing::new(String::with_capacity(
+            self.text.len() - span.len() + replacement.len(),
+        ));
+
+        text.push_str(&self.text[..span.start]);
+        text.push_str(replacement);
+        text.push_str(&self.text[span.end..]);
+
+        self.text = text;
+    }
+}
+
+/// Narrow a span of the text down to exclude leading and trailing whitespace.
+fn trimmed_span(text: &str, span: Range<usize>) -> Range<usize> {
+    let part = &text[span.clone()];
+    let start = span.start + (part.len() - part.trim_start().len());
+
+    start..start + part.trim().len()
+}
+
+/// The lines of a section in a document, from its header up to the next header or the end of the document.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SectionSpan {
+    /// The index of the header line.
+    pub header: usize,
+    /// The index after the last property or continuation line, leaving out blank lines and comments which trail it, as
+    /// they tend to belong to the next section.
+    pub body_end: usize,
+    /// The index of the next header, or the number of lines.
+    pub end: usize,
+}
+
+/// An INI document which keeps comments, blank lines, whitespace, ordering and duplicates, so that it serializes back
+/// to its exact source unless modified, and modifications only touch the lines they concern.
+///
+/// Where sections or keys are duplicated, lookups and modifications apply to the first section by a name and to the
+/// last occurrence of a key within it. Keys are matched ignoring case and values are read from their first line only,
+/// while modifications replace or remove continuation lines along with their property.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Document {
+    lines: Vec<Line>,
+}
+
+impl Document {
+    pub fn parse(source: &str) -> Self {
+        let mut document = Self {
+            lines: split_lines(source)
+                .map(|(line, ending)| Line::new(line, ending))
+                .collect(),
+        };
+
+        document.classify();
+        document
+    }
+
+    /// Classify every line anew, as a modification may change what the lines after it hold.
+    fn classify(&mut self) {
+        let mut classifier = Classifier::default();
+
+        for line in &mut self.lines {
+            line.kind = classifier.classify(&line.text);
+        }
+    }
+
+    /// The index after the continuation lines of the property at the given index, leaving out blank lines and comments
+    /// which trail them.
+    fn continuation_end(&self, index: usize) -> usize {
+        let mut end = index + 1;
+
+        for (i, line) in self.lines.iter().enumerate().skip(index + 1) {
+            match line.kind {
+                LineKind::Continuation(_) => end = i + 1,
+                LineKind::Blank | LineKind::Comment => (),
+                _ => break,
+            }
+        }
+
+        end
+    }
+
+    /// The document's text, byte-identical to its source unless modified.
+    pub fn serialize(&self) -> Zeroizing<String> {
+        let mut result = Zeroizing::new(String::with_capacity(
+            self.lines.iter().map(|l| l.text.len() + 2).sum(),
+        ));
+
+        for line in &self.lines {
+            result.push_str(&line.text);
+            result.push_str(line.ending);
+        }
+
+        result
+    }
+
+    #[cfg(test)]
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Every section in document order, including duplicates.
+    pub fn sections(&self) -> Vec<SectionSpan> {
+        let headers: Vec<usize> = (0..self.lines.len())
+            .filter(|i| self.lines[*i].section().is_some())
+            .collect();
+
+        headers
+            .iter()
+            .enumerate()
+            .map(|(n, header)| {
+                let end = headers.get(n + 1).copied().unwrap_or(self.lines.len());
+                let body_end = (header + 1..end)
+                    .rev()
+                    .find(|i| self.lines[*i].is_content())
+                    .map_or(header + 1, |i| i + 1);
+
+                SectionSpan {
+                    header: *header,
+                    body_end,
+                    end,
+                }
+            })
+            .collect()
+    }
+
+    /// The first section by the given name.
+    pub fn section(&self, name: &str) -> Option<SectionSpan> {
+        self.sections()
+            .into_iter()
+            .find(|s| self.lines[s.header].section() == Some(name))
+    }
+
+    /// The value of a key in a section, the last one if the key is duplicated.
+    #[cfg(test)]
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.get_all(section, key).pop()
+    }
+
+    /// Every value of a key in a section, in document order.
+    #[cfg(test)]
+    pub fn get_all(&self, section: &str, key: &str) -> Vec<&str> {
+        self.section(section)
+            .map(|span| {
+                self.lines[span.header + 1..span.end]
+                    .iter()
+                    .filter(|line| line.has_key(key))
+                    .filter_map(|line| line.value())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Set a key in a section, creating either if need be.
+    ///
+    /// An existing value is replaced in place, keeping the rest of its line but dropping any continuation lines, while
+    /// new keys are added as `key = value` after the last property of the section.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let span = match self.section(section) {
+            Some(span) => span,
+            None => self.push_section(section),
+        };
+
+        let existing = (span.header + 1..span.end)
+            .rev()
+            .find(|i| self.lines[*i].has_key(key));
+
+        match existing {
+            Some(i) => {
+                if let LineKind::Property(_, value_span) = self.lines[i].kind.clone() {
+                    self.lines[i].splice(value_span, value);
+                }
+
+                let end = self.continuation_end(i);
+                self.lines.drain(i + 1..end);
+                self.classify();
+            }
+            None => {
+                let line = Zeroizing::new(format!("{} = {}", key, value));
+                self.insert(span.body_end, &line);
+            }
+        }
+    }
+
+    /// Remove every occurrence of a key from a section along with its continuation lines, returning whether there was
+    /// any.
+    #[cfg(test)]
+    pub fn remove(&mut self, section: &str, key: &str) -> bool {
+        let span = match self.section(section) {
+            Some(span) => span,
+            None => return false,
+        };
+
+        let removed: Vec<usize> = (span.header + 1..span.end)
+            .filter(|i| self.lines[*i].has_key(key))
+            .collect();
+
+        for i in removed.iter().rev() {
+            let end = self.continuation_end(*i);
+            self.lines.drain(*i..end);
+        }
+
+        self.classify();
+        !removed.is_empty()
+    }
+
+    /// Append an empty section, separated from the rest of the document by a blank line.
+    pub fn push_section(&mut self, name: &str) -> SectionSpan {
+        if self.lines.iter().any(|line| line.kind != LineKind::Blank)
+            && self.lines.last().map(|line| &line.kind) != Some(&LineKind::Blank)
+        {
+            self.insert(self.lines.len(), "");
+        }
+
+        let header = self.lines.len();
+        self.insert(header, &format!("[{}]", name));
+
+        SectionSpan {
+            header,
+            body_end: header + 1,
+            end: header + 1,
+        }
+    }
+
+    /// Remove the properties, continuation lines and comments of the first section by the given name, keeping its
+    /// header and any blank lines and comments trailing it. Returns whether there was such a section.
+    pub fn clear_section(&mut self, name: &str) -> bool {
+        match self.section(name) {
+            Some(span) => {
+                self.lines.drain(span.header + 1..span.body_end);
+                self.classify();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the first section by the given name, keeping blank lines and comments trailing it, except for a blank
+    /// line which would otherwise double up with the one before the section or trail the document. Returns whether
+    /// there was such a section.
+    pub fn remove_section(&mut self, name: &str) -> bool {
+        let span = match self.section(name) {
+            Some(span) => span,
+            None => return false,
+        };
+
+        self.lines.drain(span.header..span.body_end);
+
+        let is_blank = |line: Option<&Line>| line.map(|line| &line.kind) == Some(&LineKind::Blank);
+        let before = span.header.checked_sub(1).map(|i| &self.lines[i]);
+
+        if is_blank(self.lines.get(span.header)) && (span.header == 0 || is_blank(before)) {
+            self.lines.remove(span.header);
+        } else if span.header == self.lines.len() && is_blank(before) {
+            // nothing separates the end of the document from anything anymore
+            self.lines.remove(span.header - 1);
+        }
+
+        self.classify();
+        true
+    }
+
+    /// Rename the first section by the given name, keeping the rest of its header line. Returns whether there was such
+    /// a section.
+    pub fn rename_section(&mut self, name: &str, new_name: &str) -> bool {
+        match self.section(name) {
+            Some(span) => {
+                if let LineKind::Section(name) = self.lines[span.header].kind.clone() {
+                    self.lines[span.header].splice(name, new_name);
+                }
+
+                self.classify();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Insert a line, ending lines like the rest of the document.
+    pub fn insert(&mut self, index: usize, text: &str) {
+        let ending = self
+            .lines
+            .iter()
+            .map(|line| line.ending)
+            .find(|ending| !ending.is_empty())
+            .unwrap_or("\n");
+
+        // the last line may lack a line ending, which it needs once another line follows it
+        if index == self.lines.len() {
+            if let Some(last) = self.lines.last_mut() {
+                if last.ending.is_empty() {
+                    last.ending = ending;
+                }
+            }
+        }
+
+        self.lines.insert(index, Line::new(text, ending));
+        self.classify();
+    }
+}
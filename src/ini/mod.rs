@@ -1,54 +1,367 @@
 #[cfg(test)]
 mod tests;
 
+mod document;
+
+pub(crate) use document::{Document, LineKind};
+
+use document::Classifier;
+
 use indexmap::IndexMap;
-use ini_core::{Item as IniEntity, Parser as IniParser};
 
-/// A zero-copy, parsed INI file represented as a map of section names to maps of key/value properties.
+use serde::Serialize;
+
+use zeroize::Zeroizing;
+
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+/// A parsed INI file represented as a map of section names to maps of key/value properties.
 ///
 /// Internally, it is an `IndexMap`, so it will respect section order.
 pub type Ini<'a> = IndexMap<&'a str, IniSection<'a>>;
-/// A zero-copy INI file section represented as a map of keys to optional values.
-///
-/// If a key exists in this map but its value is `None`, this means that either the value was empty or the key had no
-/// equals-sign associated with it, in `ini_core` parlance, this is an `ini_core::Item::Action`.
+/// An INI file section represented as a map of lowercase keys to values.
 ///
 /// Internally, it is an `IndexMap`, so it will respect key order.
-pub type IniSection<'a> = IndexMap<&'a str, Option<&'a str>>;
+pub type IniSection<'a> = IndexMap<Cow<'a, str>, IniValue<'a>>;
+
+/// The value of a property, which borrows from the source unless it spans several lines.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IniValue<'a> {
+    /// A value on a single line, which may be empty.
+    Value(&'a str),
+    /// A value continued on indented lines, joined by line breaks.
+    MultiLine(Zeroizing<String>),
+    /// Properties nested on indented lines after a key without a value, as in `s3 =` blocks.
+    Nested(IndexMap<&'a str, &'a str>),
+}
+
+impl<'a> IniValue<'a> {
+    /// The value as a string, unless it holds nested properties.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            IniValue::Value(value) => Some(value),
+            IniValue::MultiLine(value) => Some(value),
+            IniValue::Nested(_) => None,
+        }
+    }
+
+    /// Continue the value with an indented line, following the given number of blank lines. Returns whether the line
+    /// could be added, which nested properties need an `=` for.
+    fn continue_with(&mut self, line: &'a str, blanks: usize) -> bool {
+        match self {
+            IniValue::Value("") | IniValue::Nested(_) => match line.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    // NOTE only a valid nested property turns an empty value into nested properties
+                    if let IniValue::Value(_) = self {
+                        *self = IniValue::Nested(IndexMap::with_capacity(4));
+                    }
+
+                    if let IniValue::Nested(properties) = self {
+                        properties.insert(key.trim(), value.trim());
+                    }
+
+                    true
+                }
+                _ => false,
+            },
+            IniValue::Value(first) => {
+                let mut value = Zeroizing::new(first.to_string());
+                value.push_str(&"\n".repeat(blanks + 1));
+                value.push_str(line);
+                *self = IniValue::MultiLine(value);
+                true
+            }
+            IniValue::MultiLine(value) => {
+                value.push_str(&"\n".repeat(blanks + 1));
+                value.push_str(line);
+                true
+            }
+        }
+    }
+}
+
+/// How serious a problem found in an INI file is.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A problem found in an INI file.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// A line which is neither a section header, a property nor a comment, such as a header missing its `]` or a key
+    /// without an `=`, or a nested property without an `=`.
+    MalformedLine,
+    /// A key before the first section header, which is ignored.
+    KeyOutsideSection { key: String },
+    /// A section defined again, which replaces the earlier one entirely, while the AWS CLI refuses the file.
+    DuplicateSection { name: String, first_line: usize },
+    /// A key defined again within a section, ignoring case, whose value replaces the earlier one, while the AWS CLI
+    /// refuses the file.
+    DuplicateKey { key: String, first_line: usize },
+    /// A section missing a key it needs.
+    MissingKey { section: String, key: String },
+    /// A key which isn't known to be used in this kind of file.
+    UnknownKey { key: String },
+    /// A value containing whitespace, which none of the known values may.
+    WhitespaceInValue { key: String },
+}
+
+impl DiagnosticKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            DiagnosticKind::KeyOutsideSection { .. } | DiagnosticKind::UnknownKey { .. } => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticKind::MalformedLine => write!(f, "malformed line"),
+            DiagnosticKind::KeyOutsideSection { key } => {
+                write!(f, "key '{}' is outside of any section and ignored", key)
+            }
+            DiagnosticKind::DuplicateSection { name, first_line } => write!(
+                f,
+                "section '{}' is defined again, replacing the one on line {}",
+                name, first_line
+            ),
+            DiagnosticKind::DuplicateKey { key, first_line } => write!(
+                f,
+                "key '{}' is defined again, replacing the one on line {}",
+                key, first_line
+            ),
+            DiagnosticKind::MissingKey { section, key } => {
+                write!(f, "section '{}' is missing '{}'", section, key)
+            }
+            DiagnosticKind::UnknownKey { key } => write!(f, "unknown key '{}'", key),
+            DiagnosticKind::WhitespaceInValue { key } => {
+                write!(f, "value of '{}' contains whitespace", key)
+            }
+        }
+    }
+}
+
+/// A problem found in an INI file, at a 1-based line and column.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    #[serde(flatten)]
+    pub kind: DiagnosticKind,
+}
 
-/// Parse a string reference into an `Ini` map of `IniSections`.
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line,
+            self.column,
+            self.severity(),
+            self.kind
+        )
+    }
+}
+
+/// An entity of an INI file, see `Classifier` for how lines are told apart.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum IniEntity<'a> {
+    Blank,
+    Comment,
+    Section(&'a str),
+    /// A key as written, which the AWS CLI lowercases, and a value.
+    Property(&'a str, &'a str),
+    /// An indented line continuing the value of the property before it.
+    Continuation(&'a str),
+    Malformed,
+}
+
+/// An entity parsed from an INI file along with the 1-based line and column it starts at.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Entry<'a> {
+    pub line: usize,
+    pub column: usize,
+    pub entity: IniEntity<'a>,
+}
+
+/// Every entity of an INI file in order, with its position.
+pub(crate) fn entries(source: &str) -> impl Iterator<Item = Entry<'_>> {
+    let mut classifier = Classifier::default();
+
+    document::split_lines(source)
+        .enumerate()
+        .map(move |(i, (text, _))| {
+            let entity = match classifier.classify(text) {
+                LineKind::Blank => IniEntity::Blank,
+                LineKind::Comment => IniEntity::Comment,
+                LineKind::Section(name) => IniEntity::Section(&text[name]),
+                LineKind::Property(key, value) => IniEntity::Property(&text[key], &text[value]),
+                LineKind::Continuation(span) => IniEntity::Continuation(&text[span]),
+                LineKind::Malformed => IniEntity::Malformed,
+            };
+
+            Entry {
+                line: i + 1,
+                column: match entity {
+                    IniEntity::Property(part, _) | IniEntity::Continuation(part) => {
+                        column(source, part)
+                    }
+                    _ => 1,
+                },
+                entity,
+            }
+        })
+}
+
+/// The 1-based column a slice of the source starts at, counting characters.
+pub(crate) fn column(source: &str, part: &str) -> usize {
+    let offset = (part.as_ptr() as usize)
+        .saturating_sub(source.as_ptr() as usize)
+        .min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+    source[line_start..offset].chars().count() + 1
+}
+
+/// Parse a string reference into an `Ini` map of `IniSections`, logging any problems found.
+///
+/// The file is read the way the AWS CLI reads its shared configuration and credentials files, see `Classifier`: keys
+/// are lowercased, indented lines continue the value before them and a key without a value followed by indented
+/// `key = value` lines holds those as nested properties.
 ///
 /// Generics make it possible to pass a `&String`, `&str`, or even a `&Cow<str>`.
 pub fn parse<'a, S: AsRef<str> + ?Sized>(source: &'a S) -> Ini<'a> {
+    let (map, diagnostics) = parse_with_diagnostics(source);
+
+    for diagnostic in diagnostics {
+        log::warn!("Problem parsing INI file at {}", diagnostic);
+    }
+
+    map
+}
+
+/// Parse a string reference into an `Ini` map of `IniSections`, along with the problems found, which are either
+/// malformed lines or entities which the map can't hold: keys outside of any section, duplicate sections and duplicate
+/// keys. Duplicates replace the earlier entity.
+pub fn parse_with_diagnostics<'a, S: AsRef<str> + ?Sized>(
+    source: &'a S,
+) -> (Ini<'a>, Vec<Diagnostic>) {
+    let source = source.as_ref();
+
     // NOTE we assume that a given file won't have more than 16 sections, so overallocate early.
     let mut map = Ini::with_capacity(16);
-    let parser = IniParser::new(source.as_ref()).auto_trim(true);
+    let mut diagnostics = Vec::new();
+
+    // the line each section and each key of the current section was defined on, for reporting duplicates
+    let mut sections: IndexMap<&str, usize> = IndexMap::with_capacity(16);
+    let mut keys: IndexMap<Cow<str>, usize> = IndexMap::with_capacity(4);
 
     let mut current_section = None;
+    // the key whose value continuation lines add to, and the number of blank lines since its last line
+    let mut current_key: Option<Cow<str>> = None;
+    let mut blanks = 0;
+
+    for entry in entries(source) {
+        let mut diagnose = |kind| {
+            diagnostics.push(Diagnostic {
+                line: entry.line,
+                column: entry.column,
+                kind,
+            })
+        };
 
-    for (_line, entity) in parser.enumerate() {
-        match entity {
-            IniEntity::Comment(_) | IniEntity::Blank => (),
-            IniEntity::Error(e) => {
-                log::warn!("Error parsing INI file: {}", e);
+        match entry.entity {
+            IniEntity::Blank => {
+                blanks += 1;
+                continue;
             }
+            IniEntity::Comment => continue,
+            IniEntity::Malformed => diagnose(DiagnosticKind::MalformedLine),
             IniEntity::Section(section) => {
+                if let Some(first_line) = sections.insert(section, entry.line) {
+                    diagnose(DiagnosticKind::DuplicateSection {
+                        name: section.to_string(),
+                        first_line,
+                    });
+                }
+
+                keys.clear();
+                current_key = None;
                 current_section = Some(section);
                 // NOTE we assume that each section will generally have no more than four keys
                 map.insert(section, IniSection::with_capacity(4));
             }
-            IniEntity::Action(key) => {
-                if let Some(section) = current_section {
-                    map.get_mut(section).unwrap().insert(key, None);
-                }
-            }
             IniEntity::Property(key, value) => {
-                if let Some(section) = current_section {
-                    map.get_mut(section).unwrap().insert(key, Some(value));
+                let key = lowercase(key);
+
+                current_key = match current_section {
+                    Some(section) => {
+                        if let Some(first_line) = keys.insert(key.clone(), entry.line) {
+                            diagnose(DiagnosticKind::DuplicateKey {
+                                key: key.to_string(),
+                                first_line,
+                            });
+                        }
+
+                        map[section].insert(key.clone(), IniValue::Value(value));
+                        Some(key)
+                    }
+                    None => {
+                        diagnose(DiagnosticKind::KeyOutsideSection {
+                            key: key.to_string(),
+                        });
+                        None
+                    }
+                };
+            }
+            IniEntity::Continuation(line) => {
+                let value = current_section
+                    .zip(current_key.as_deref())
+                    .and_then(|(section, key)| map[section].get_mut(key));
+
+                if let Some(value) = value {
+                    if !value.continue_with(line, blanks) {
+                        diagnose(DiagnosticKind::MalformedLine);
+                    }
                 }
             }
         }
+
+        blanks = 0;
     }
 
-    map
+    (map, diagnostics)
+}
+
+/// Lowercase a key the way the AWS CLI does, borrowing it if it already is.
+fn lowercase(key: &str) -> Cow<'_, str> {
+    if key.chars().any(char::is_uppercase) {
+        Cow::Owned(key.to_lowercase())
+    } else {
+        Cow::Borrowed(key)
+    }
 }
@@ -1,4 +1,14 @@
+pub mod agent;
+pub mod config;
+pub mod ecs;
+pub mod edit;
+pub mod export;
+pub mod imds;
 pub(crate) mod ini;
+pub mod lint;
+pub mod list;
+pub mod query;
+pub mod shell;
 pub(crate) mod utils;
 
 mod loader;
@@ -7,10 +17,91 @@ mod tests;
 
 use indexmap::IndexMap;
 
+use serde::{Deserialize, Serialize};
+
 use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+pub use loader::{
+    AwsConfigFile, AwsCredentials, AwsCredentialsFile, AwsProfile, Error, FileSource, KeyType,
+};
+pub use utils::fs::AtomicFile;
+
+/// The most suggestions offered for a profile name which can't be found.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// A profile name which couldn't be found, with the closest known profiles.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProfileNotFound {
+    pub name: String,
+    /// Suggestions in order of similarity, most similar first.
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Display for ProfileNotFound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unable to find profile '{}'", self.name)?;
+
+        if !self.suggestions.is_empty() {
+            let suggestions: Vec<String> = self.suggestions.iter().map(|s| s.to_string()).collect();
+            write!(f, ", did you mean {}?", suggestions.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
 
-pub use loader::{AwsCredentials, AwsCredentialsFile, AwsProfile, Error};
+impl std::error::Error for ProfileNotFound {}
 
+/// A profile name similar to one which couldn't be found, with the URIs of every profile by that name.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub name: String,
+    /// The profiles with this name in priority order, more than one meaning the bare name is ambiguous.
+    pub uris: Vec<String>,
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.uris.as_slice() {
+            [uri] => f.write_str(uri),
+            uris => write!(f, "{} (one of {})", self.name, uris.join(", ")),
+        }
+    }
+}
+
+/// A bare profile name matching profiles in more than one file, which strict lookups refuse to pick between.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AmbiguousProfile {
+    pub name: String,
+    /// The URIs of every matching profile in priority order, any of which can be used instead of the bare name.
+    pub uris: Vec<String>,
+}
+
+impl Display for AmbiguousProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "profile '{}' is defined in more than one file, use one of {}",
+            self.name,
+            self.uris.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for AmbiguousProfile {}
+
+/// A bare profile name defined in more than one file, and which of the profiles wins.
+#[derive(Debug)]
+pub struct Conflict<'a> {
+    pub name: &'a str,
+    /// The profile bare name lookups resolve to.
+    pub winner: &'a AwsProfile,
+    /// The profiles hidden by the winner, in priority order.
+    pub shadowed: Vec<&'a AwsProfile>,
+}
+
+#[derive(Default)]
 pub struct AwsProfileLookup {
     files: BTreeSet<AwsCredentialsFile>,
 }
@@ -100,6 +191,138 @@ impl<'a> AwsProfileLookup {
         None
     }
 
+    /// Resolves a profile the way the command-line does: first by URI, then by bare name.
+    pub fn resolve<S: AsRef<str>>(&'a self, name: S) -> Option<&'a AwsProfile> {
+        self.by_uri(name.as_ref())
+            .or_else(|| self.by_name(name.as_ref()))
+    }
+
+    /// Find every profile with the given access key id, in priority order.
+    ///
+    /// The same key may be stored under several profiles, e.g. in both a plain-text and an encrypted file.
+    pub fn by_access_key_id<S: AsRef<str>>(&'a self, access_key_id: S) -> Vec<&'a AwsProfile> {
+        self.files
+            .iter()
+            .rev()
+            .flat_map(|file| file.profiles.values())
+            .filter(|profile| profile.access_key_id.eq(access_key_id.as_ref()))
+            .collect()
+    }
+
+    /// Resolve a profile like [AwsProfileLookup::resolve], suggesting similar profiles if it can't be found.
+    pub fn find<S: AsRef<str>>(&'a self, name: S) -> Result<&'a AwsProfile, ProfileNotFound> {
+        self.resolve(name.as_ref()).ok_or_else(|| ProfileNotFound {
+            name: name.as_ref().to_string(),
+            suggestions: self.suggest(name.as_ref()),
+        })
+    }
+
+    /// Resolve a profile like [AwsProfileLookup::find], but refuse bare names matching profiles in more than one file.
+    ///
+    /// Errors are either a [ProfileNotFound] or an [AmbiguousProfile].
+    pub fn find_strict<S: AsRef<str>>(&'a self, name: S) -> Result<&'a AwsProfile, Error> {
+        if let Some(profile) = self.by_uri(name.as_ref()) {
+            return Ok(profile);
+        }
+
+        match self.all_by_name(name.as_ref()).as_slice() {
+            [] => Err(Box::new(ProfileNotFound {
+                name: name.as_ref().to_string(),
+                suggestions: self.suggest(name.as_ref()),
+            })),
+            [profile] => Ok(profile),
+            profiles => Err(Box::new(AmbiguousProfile {
+                name: name.as_ref().to_string(),
+                uris: profiles.iter().map(|p| p.uri()).collect(),
+            })),
+        }
+    }
+
+    /// Every profile with the given name, in priority order.
+    pub fn all_by_name<S: AsRef<str>>(&'a self, name: S) -> Vec<&'a AwsProfile> {
+        self.files
+            .iter()
+            .rev()
+            .filter_map(|f| f.profiles.get(name.as_ref()))
+            .collect()
+    }
+
+    /// Every bare name defined in more than one file, ordered by name.
+    pub fn conflicts(&'a self) -> Vec<Conflict<'a>> {
+        let mut names: Vec<&str> = self.list().into_iter().map(|p| p.name.as_str()).collect();
+
+        names.sort_unstable();
+        names.dedup();
+
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let mut profiles = self.all_by_name(name);
+
+                if profiles.len() > 1 {
+                    let winner = profiles.remove(0);
+
+                    Some(Conflict {
+                        name,
+                        winner,
+                        shadowed: profiles,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Suggest profiles with names or URIs similar to the given name, most similar first.
+    ///
+    /// Names and URIs which start with the given name come first, then those containing it, then those within a small
+    /// edit distance of it, all compared case-insensitively. Each suggested name lists every profile by that name, so
+    /// that ambiguous bare names can be told apart.
+    pub fn suggest<S: AsRef<str>>(&'a self, name: S) -> Vec<Suggestion> {
+        let query = name.as_ref().to_lowercase();
+
+        let mut scored: Vec<(usize, &AwsProfile)> = self
+            .list()
+            .into_iter()
+            .filter_map(|p| {
+                let name_score = similarity(query.as_str(), p.name.to_lowercase().as_str());
+                let uri_score = similarity(query.as_str(), p.uri().to_lowercase().as_str());
+
+                name_score
+                    .into_iter()
+                    .chain(uri_score)
+                    .min()
+                    .map(|s| (s, p))
+            })
+            .collect();
+
+        scored.sort_by(|(a, p), (b, q)| a.cmp(b).then_with(|| p.name.cmp(&q.name)));
+
+        let mut suggestions: Vec<Suggestion> = Vec::new();
+
+        for (_, profile) in scored {
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+
+            if suggestions.iter().any(|s| s.name == profile.name) {
+                continue;
+            }
+
+            suggestions.push(Suggestion {
+                name: profile.name.clone(),
+                uris: self
+                    .all_by_name(&profile.name)
+                    .into_iter()
+                    .map(|p| p.uri())
+                    .collect(),
+            });
+        }
+
+        suggestions
+    }
+
     /// List _all_ profiles regardless of overlapping aliases.
     pub fn list(&'a self) -> Vec<&'a AwsProfile> {
         // reserve at _least_ enough for the amount of files we have
@@ -124,6 +347,24 @@ impl<'a> AwsProfileLookup {
             }
         }
 
-        storage.values().map(|x| *x).collect()
+        storage.values().copied().collect()
+    }
+}
+
+/// How similar a candidate is to the query, lower being more similar, or `None` if it isn't similar at all.
+fn similarity(query: &str, candidate: &str) -> Option<usize> {
+    if candidate.starts_with(query) {
+        Some(0)
+    } else if candidate.contains(query) {
+        Some(1)
+    } else {
+        let distance = utils::edit_distance(query, candidate);
+
+        // NOTE allow roughly one typo for every three characters typed
+        if distance <= (query.chars().count() / 3).max(1) {
+            Some(1 + distance)
+        } else {
+            None
+        }
     }
 }
@@ -0,0 +1,179 @@
+#[cfg(test)]
+mod tests;
+
+pub use crate::ini::{Diagnostic, DiagnosticKind, Severity};
+
+use crate::ini::{self, IniEntity};
+use crate::loader::credentials_file::{
+    INI_ACCESS_KEY_ID_TAG, INI_EXPIRATION_TAGS, INI_REGION_TAG, INI_SECRET_ACCESS_KEY_TAG,
+    INI_SESSION_TOKEN_TAG,
+};
+use crate::{edit, Error, FileSource};
+
+/// Keys of the AWS CLI's shared configuration which may appear in credential files, besides the ones read here.
+const KNOWN_KEYS: &[&str] = &[
+    "aws_security_token",
+    "ca_bundle",
+    "cli_pager",
+    "credential_process",
+    "credential_source",
+    "duration_seconds",
+    "endpoint_url",
+    "external_id",
+    "max_attempts",
+    "mfa_serial",
+    "output",
+    "retry_mode",
+    "role_arn",
+    "role_session_name",
+    "s3",
+    "source_profile",
+    "sso_account_id",
+    "sso_region",
+    "sso_role_name",
+    "sso_session",
+    "sso_start_url",
+    "web_identity_token_file",
+];
+
+/// Keys which make the AWS CLI source credentials from elsewhere, so that a section with one needs no static keys.
+const CREDENTIAL_SOURCE_KEYS: &[&str] = &[
+    "credential_process",
+    "role_arn",
+    "sso_session",
+    "sso_start_url",
+    "web_identity_token_file",
+];
+
+/// Keys whose values can never contain whitespace.
+const WHITESPACE_FREE_KEYS: &[&str] = &[
+    INI_ACCESS_KEY_ID_TAG,
+    INI_SECRET_ACCESS_KEY_TAG,
+    INI_SESSION_TOKEN_TAG,
+    INI_REGION_TAG,
+];
+
+/// Find problems in the source of a credentials file, in the order they appear.
+///
+/// Besides the problems found while parsing, see `ini::parse_with_diagnostics`, these are sections missing either an
+/// access key id or a secret access key, unknown keys and whitespace in values which can't have any, including line
+/// breaks of values continued on indented lines.
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    let (_, mut diagnostics) = ini::parse_with_diagnostics(source);
+
+    // the section being checked, with the line it starts on and the lowercase keys found so far
+    let mut section: Option<(&str, usize, Vec<String>)> = None;
+    // the key whose value continuation lines add to, if it can't contain whitespace
+    let mut whitespace_free_key = None;
+
+    for entry in ini::entries(source) {
+        let mut diagnose = |column, kind| {
+            diagnostics.push(Diagnostic {
+                line: entry.line,
+                column,
+                kind,
+            })
+        };
+
+        match entry.entity {
+            IniEntity::Section(name) => {
+                if let Some((name, line, keys)) = section.take() {
+                    check_section(name, line, &keys, &mut diagnostics);
+                }
+                section = Some((name, entry.line, vec![]));
+                whitespace_free_key = None;
+            }
+            IniEntity::Property(key, value) => {
+                let keys = match &mut section {
+                    Some((_, _, keys)) => keys,
+                    // NOTE already reported while parsing
+                    None => continue,
+                };
+
+                let lowercase = key.to_lowercase();
+
+                if !is_known(&lowercase) {
+                    diagnose(
+                        entry.column,
+                        DiagnosticKind::UnknownKey {
+                            key: key.to_string(),
+                        },
+                    );
+                }
+
+                whitespace_free_key = WHITESPACE_FREE_KEYS
+                    .contains(&lowercase.as_str())
+                    .then_some(key);
+
+                if whitespace_free_key.is_some() && value.contains(char::is_whitespace) {
+                    diagnose(
+                        ini::column(source, value),
+                        DiagnosticKind::WhitespaceInValue {
+                            key: key.to_string(),
+                        },
+                    );
+                }
+
+                keys.push(lowercase);
+            }
+            // NOTE a value continued on another line contains a line break
+            IniEntity::Continuation(_) => {
+                if let Some(key) = whitespace_free_key {
+                    diagnose(
+                        entry.column,
+                        DiagnosticKind::WhitespaceInValue {
+                            key: key.to_string(),
+                        },
+                    );
+                }
+            }
+            IniEntity::Malformed => whitespace_free_key = None,
+            _ => (),
+        }
+    }
+
+    if let Some((name, line, keys)) = section {
+        check_section(name, line, &keys, &mut diagnostics);
+    }
+
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+/// Check that a section starting on the given line has both static keys, unless credentials are sourced elsewhere.
+fn check_section(name: &str, line: usize, keys: &[String], diagnostics: &mut Vec<Diagnostic>) {
+    let has = |key: &str| keys.iter().any(|k| k == key);
+
+    if CREDENTIAL_SOURCE_KEYS.iter().any(|key| has(key))
+        && !has(INI_ACCESS_KEY_ID_TAG)
+        && !has(INI_SECRET_ACCESS_KEY_TAG)
+    {
+        return;
+    }
+
+    for key in [INI_ACCESS_KEY_ID_TAG, INI_SECRET_ACCESS_KEY_TAG] {
+        if !has(key) {
+            diagnostics.push(Diagnostic {
+                line,
+                column: 1,
+                kind: DiagnosticKind::MissingKey {
+                    section: name.to_string(),
+                    key: key.to_string(),
+                },
+            });
+        }
+    }
+}
+
+fn is_known(key: &str) -> bool {
+    WHITESPACE_FREE_KEYS.contains(&key)
+        || INI_EXPIRATION_TAGS.contains(&key)
+        || KNOWN_KEYS.contains(&key)
+}
+
+/// Find problems in a credentials file, decrypting it if it is encrypted.
+pub async fn lint_file(file: &FileSource) -> Result<Vec<Diagnostic>, Error> {
+    let source = edit::read(file).await?;
+
+    Ok(lint(&source))
+}
@@ -0,0 +1,727 @@
+#[cfg(test)]
+mod tests;
+
+use crate::query::ProfileQuery;
+use crate::{AwsConfigFile, AwsProfile, AwsProfileLookup, Conflict, Error};
+
+use indexmap::IndexMap;
+
+use prettytable::format::{self, LinePosition, LineSeparator};
+use prettytable::{Cell, Row as TableRow, Table};
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// The formats profiles can be listed in, as accepted by `ListFormat::from_str`.
+pub const LIST_FORMATS: &[&str] = &[
+    "table", "plain", "csv", "json", "ndjson", "yaml", "toml", "tree",
+];
+
+/// The columns which can be listed, as accepted by `Column::from_str`.
+pub const LIST_COLUMNS: &[&str] = &[
+    "name",
+    "uri",
+    "priority",
+    "shadowed_by",
+    "file",
+    "access_key_id",
+    "key_type",
+    "session_token",
+    "region",
+    "modified",
+    "age",
+    "encryption",
+];
+
+/// The columns listed unless others are chosen, comma-separated.
+pub const DEFAULT_LIST_COLUMNS: &str = "name,uri,priority,shadowed_by,file";
+
+/// Output format for listing profiles.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ListFormat {
+    /// Default table output format.
+    #[default]
+    Table,
+    /// Tab-separated values, one record per line, for parsing with command-line tools.
+    ///
+    /// Backslashes, tabs, newlines and carriage returns in values are escaped as `\\`, `\t`, `\n` and `\r`, unless
+    /// records are NUL-terminated, in which case values are written as they are.
+    Plain,
+    /// CSV format.
+    Csv,
+    /// JSON format.
+    Json,
+    /// Newline-delimited JSON, one compact object per profile, for streaming into tools like `jq`.
+    Ndjson,
+    /// YAML format, a sequence of mappings.
+    Yaml,
+    /// TOML format, an array of tables.
+    Toml,
+    /// Profiles grouped under the file they're defined in.
+    Tree,
+}
+
+impl Display for ListFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            ListFormat::Table => "table",
+            ListFormat::Plain => "plain",
+            ListFormat::Csv => "csv",
+            ListFormat::Json => "json",
+            ListFormat::Ndjson => "ndjson",
+            ListFormat::Yaml => "yaml",
+            ListFormat::Toml => "toml",
+            ListFormat::Tree => "tree",
+        })
+    }
+}
+
+impl FromStr for ListFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "table" => Ok(ListFormat::Table),
+            "plain" => Ok(ListFormat::Plain),
+            "csv" => Ok(ListFormat::Csv),
+            "json" => Ok(ListFormat::Json),
+            "ndjson" => Ok(ListFormat::Ndjson),
+            "yaml" => Ok(ListFormat::Yaml),
+            "toml" => Ok(ListFormat::Toml),
+            "tree" => Ok(ListFormat::Tree),
+            _ => Err(format!("unknown format {}", s).into()),
+        }
+    }
+}
+
+/// A column of non-secret profile metadata to list.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Column {
+    Name,
+    Uri,
+    Priority,
+    /// The profile shadowing this one, if any.
+    ShadowedBy,
+    File,
+    /// The masked access key id, e.g. `AKIA…WXYZ`.
+    AccessKeyId,
+    /// Whether the access key id is long-term or temporary.
+    KeyType,
+    /// Whether the profile has a session token.
+    SessionToken,
+    Region,
+    /// When the profile's file was last modified.
+    Modified,
+    /// How long ago the profile's file was last modified.
+    Age,
+    /// How the profile's file is encrypted, if at all.
+    Encryption,
+}
+
+impl Column {
+    /// The title of the column in table output.
+    fn title(&self) -> String {
+        match *self {
+            Column::Name => "profile".to_string(),
+            Column::Uri => "prefix/profile".to_string(),
+            column => column.to_string(),
+        }
+    }
+
+    /// The prettytable style of the column in table output.
+    fn style(&self) -> &'static str {
+        match *self {
+            Column::Name => "Fg",
+            Column::Uri => "Fcb",
+            Column::ShadowedBy => "Fy",
+            Column::File => "FD",
+            _ => "",
+        }
+    }
+}
+
+impl Display for Column {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Column::Name => "name",
+            Column::Uri => "uri",
+            Column::Priority => "priority",
+            Column::ShadowedBy => "shadowed_by",
+            Column::File => "file",
+            Column::AccessKeyId => "access_key_id",
+            Column::KeyType => "key_type",
+            Column::SessionToken => "session_token",
+            Column::Region => "region",
+            Column::Modified => "modified",
+            Column::Age => "age",
+            Column::Encryption => "encryption",
+        })
+    }
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "name" => Ok(Column::Name),
+            "uri" => Ok(Column::Uri),
+            "priority" => Ok(Column::Priority),
+            "shadowed_by" => Ok(Column::ShadowedBy),
+            "file" => Ok(Column::File),
+            "access_key_id" => Ok(Column::AccessKeyId),
+            "key_type" => Ok(Column::KeyType),
+            "session_token" => Ok(Column::SessionToken),
+            "region" => Ok(Column::Region),
+            "modified" => Ok(Column::Modified),
+            "age" => Ok(Column::Age),
+            "encryption" => Ok(Column::Encryption),
+            _ => Err(format!("unknown column {}", s).into()),
+        }
+    }
+}
+
+/// Options controlling how profiles are listed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListOptions {
+    /// The columns to list, in order.
+    pub columns: Vec<Column>,
+    /// Exclude the column titles from table and CSV output.
+    pub no_header: bool,
+    /// Separate and terminate plain fields with NUL rather than tabs and newlines, writing values without escaping them.
+    pub null: bool,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            columns: DEFAULT_LIST_COLUMNS
+                .split(',')
+                .map(|c| c.parse().unwrap())
+                .collect(),
+            no_header: false,
+            null: false,
+        }
+    }
+}
+
+/// A profile to list, along with what's known about it beyond the profile itself.
+pub struct Row<'a> {
+    /// The index of the profile in `AwsProfileLookup::list`.
+    pub priority: usize,
+    pub profile: &'a AwsProfile,
+    /// The URI of the profile shadowing this one, if any.
+    pub shadowed_by: Option<String>,
+    pub region: Option<&'a str>,
+}
+
+impl Row<'_> {
+    fn value(&self, column: Column) -> Value {
+        match column {
+            Column::Name => Value::Text(self.profile.name.clone()),
+            Column::Uri => Value::Text(self.profile.uri()),
+            Column::Priority => Value::Number(self.priority as u64),
+            Column::ShadowedBy => self.shadowed_by.clone().map_or(Value::Empty, Value::Text),
+            Column::File => Value::Text(self.profile.file.path.display().to_string()),
+            Column::AccessKeyId => Value::Text(self.profile.masked_access_key_id()),
+            Column::KeyType => Value::Text(self.profile.key_type().to_string()),
+            Column::SessionToken => Value::Flag(self.profile.session_token.is_some()),
+            Column::Region => self
+                .region
+                .map_or(Value::Empty, |r| Value::Text(r.to_string())),
+            Column::Modified => self.modified().map_or(Value::Empty, |modified| {
+                Value::Text(humantime::format_rfc3339_seconds(modified).to_string())
+            }),
+            Column::Age => self
+                .modified()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .map_or(Value::Empty, Value::Age),
+            Column::Encryption => {
+                Value::Text(self.profile.file.encryption().unwrap_or("none").to_string())
+            }
+        }
+    }
+
+    fn record(&self, columns: &[Column]) -> Record {
+        Record(
+            columns
+                .iter()
+                .map(|column| (column.to_string(), self.value(*column)))
+                .collect(),
+        )
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.profile.file.path)
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+/// A listed value, kept typed so JSON output can use numbers and booleans.
+enum Value {
+    Text(String),
+    Number(u64),
+    Flag(bool),
+    Age(Duration),
+    Empty,
+}
+
+impl Value {
+    /// The value as text, using `empty` for missing values.
+    fn text(&self, empty: &str) -> String {
+        match self {
+            Value::Text(text) => text.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Flag(true) => "yes".to_string(),
+            Value::Flag(false) => "no".to_string(),
+            Value::Age(age) => {
+                let seconds = age.as_secs();
+
+                match seconds {
+                    0..=59 => format!("{}s", seconds),
+                    60..=3599 => format!("{}m", seconds / 60),
+                    3600..=86399 => format!("{}h", seconds / 3600),
+                    _ => format!("{}d", seconds / 86400),
+                }
+            }
+            Value::Empty => empty.to_string(),
+        }
+    }
+
+    /// The value as a YAML or TOML scalar, or `None` if missing.
+    fn scalar(&self) -> Option<String> {
+        match self {
+            // NOTE JSON strings are valid YAML and TOML strings, save for DEL, which both require to be escaped
+            Value::Text(text) => Some(
+                serde_json::to_string(text)
+                    .expect("unable to quote string")
+                    .replace('\u{7f}', "\\u007F"),
+            ),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Flag(flag) => Some(flag.to_string()),
+            Value::Age(age) => Some(age.as_secs().to_string()),
+            Value::Empty => None,
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Text(text) => serializer.serialize_str(text),
+            Value::Number(n) => serializer.serialize_u64(*n),
+            Value::Flag(flag) => serializer.serialize_bool(*flag),
+            // NOTE ages are given in seconds, which is easier to work with than a rendered duration
+            Value::Age(age) => serializer.serialize_u64(age.as_secs()),
+            Value::Empty => serializer.serialize_none(),
+        }
+    }
+}
+
+/// Named values in order, as written by every format but tables and trees.
+struct Record(Vec<(String, Value)>);
+
+impl Serialize for Record {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+
+        for (key, value) in self.0.iter() {
+            map.serialize_entry(key, value)?;
+        }
+
+        map.end()
+    }
+}
+
+/// The profiles matching a query, noting which are shadowed by a profile of the same name and their regions.
+pub fn rows<'a>(
+    lookup: &'a AwsProfileLookup,
+    query: &ProfileQuery,
+    config: &'a AwsConfigFile,
+) -> Vec<Row<'a>> {
+    let winners: HashMap<&str, &AwsProfile> = lookup
+        .list_aliased()
+        .into_iter()
+        .map(|p| (p.name.as_str(), p))
+        .collect();
+
+    lookup
+        .query(query)
+        .into_iter()
+        .map(|(priority, profile)| Row {
+            priority,
+            profile,
+            shadowed_by: winners
+                .get(profile.name.as_str())
+                .filter(|w| !std::ptr::eq(**w, profile))
+                .map(|w| w.uri()),
+            region: config.region(profile),
+        })
+        .collect()
+}
+
+/// Print profiles to standard output, in color if printing a table to a terminal.
+pub fn print_profiles(rows: &[Row], format: ListFormat, options: &ListOptions) -> io::Result<()> {
+    match format {
+        ListFormat::Table => {
+            profiles_table(rows, options).printstd();
+            Ok(())
+        }
+        format => write_profiles(&mut LineWriter::new(io::stdout()), rows, format, options),
+    }
+}
+
+/// Write profiles in the given format.
+pub fn write_profiles<W: Write>(
+    writer: &mut W,
+    rows: &[Row],
+    format: ListFormat,
+    options: &ListOptions,
+) -> io::Result<()> {
+    let records = || -> Vec<Record> {
+        rows.iter()
+            .map(|row| row.record(&options.columns))
+            .collect()
+    };
+
+    match format {
+        ListFormat::Table => profiles_table(rows, options).print(writer).map(|_| ()),
+        ListFormat::Tree => write_profiles_tree(writer, rows, options),
+        ListFormat::Plain => write_plain(writer, &records(), options),
+        ListFormat::Csv => {
+            let header: Vec<String> = options.columns.iter().map(|c| c.to_string()).collect();
+            write_csv(writer, &header, &records(), options)
+        }
+        format => write_records(writer, format, "profiles", &records()),
+    }
+}
+
+/// Print shadowed profiles to standard output, in color if printing a table to a terminal.
+pub fn print_conflicts(
+    conflicts: &[Conflict],
+    format: ListFormat,
+    options: &ListOptions,
+) -> io::Result<()> {
+    match format {
+        ListFormat::Table => {
+            conflicts_table(conflicts, options).printstd();
+            Ok(())
+        }
+        format => write_conflicts(
+            &mut LineWriter::new(io::stdout()),
+            conflicts,
+            format,
+            options,
+        ),
+    }
+}
+
+/// Write every shadowed profile along with the profile which wins in the given format, ignoring the columns.
+pub fn write_conflicts<W: Write>(
+    writer: &mut W,
+    conflicts: &[Conflict],
+    format: ListFormat,
+    options: &ListOptions,
+) -> io::Result<()> {
+    let mut records = Vec::new();
+
+    for conflict in conflicts.iter() {
+        for profile in conflict.shadowed.iter() {
+            records.push(Record(vec![
+                ("name".into(), Value::Text(conflict.name.into())),
+                ("winner".into(), Value::Text(conflict.winner.uri())),
+                ("shadowed".into(), Value::Text(profile.uri())),
+                (
+                    "file".into(),
+                    Value::Text(profile.file.home_path().display().to_string()),
+                ),
+            ]));
+        }
+    }
+
+    match format {
+        ListFormat::Table => conflicts_table(conflicts, options)
+            .print(writer)
+            .map(|_| ()),
+        ListFormat::Tree => write_conflicts_tree(writer, conflicts),
+        ListFormat::Plain => write_plain(writer, &records, options),
+        ListFormat::Csv => {
+            let header = ["name", "winner", "shadowed", "file"].map(String::from);
+            write_csv(writer, &header, &records, options)
+        }
+        format => write_records(writer, format, "conflicts", &records),
+    }
+}
+
+fn table(titles: TableRow, options: &ListOptions) -> Table {
+    let mut table = Table::new();
+    let mut format = format::FormatBuilder::new()
+        .padding(0, 0)
+        .column_separator(' ');
+
+    if !options.no_header {
+        format = format.separator(LinePosition::Title, LineSeparator::new('―', ' ', ' ', ' '));
+        // FIXME can space cells via Cell::with_hspan(x)
+        table.set_titles(titles);
+    }
+
+    table.set_format(format.build());
+    table
+}
+
+fn profiles_table(rows: &[Row], options: &ListOptions) -> Table {
+    let titles = options
+        .columns
+        .iter()
+        .map(|c| Cell::new(&c.title()))
+        .collect();
+    let mut table = table(titles, options);
+
+    for row in rows {
+        table.add_row(
+            options
+                .columns
+                .iter()
+                .map(|column| {
+                    let text = match column {
+                        Column::File => row.profile.file.home_path().display().to_string(),
+                        Column::Priority => format!("{:02}", row.priority),
+                        column => row.value(*column).text(""),
+                    };
+
+                    Cell::new(&text).style_spec(column.style())
+                })
+                .collect(),
+        );
+    }
+
+    table
+}
+
+fn conflicts_table(conflicts: &[Conflict], options: &ListOptions) -> Table {
+    let titles = ["profile", "winner", "shadowed", "file"]
+        .iter()
+        .map(|t| Cell::new(t))
+        .collect();
+    let mut table = table(titles, options);
+
+    for conflict in conflicts.iter() {
+        for profile in conflict.shadowed.iter() {
+            table.add_row(TableRow::new(vec![
+                Cell::new(conflict.name).style_spec("Fg"),
+                Cell::new(&conflict.winner.uri()).style_spec("Fcb"),
+                Cell::new(&profile.uri()).style_spec("Fc"),
+                Cell::new(&profile.file.home_path().display().to_string()).style_spec("FD"),
+            ]));
+        }
+    }
+
+    table
+}
+
+fn write_plain<W: Write>(
+    writer: &mut W,
+    records: &[Record],
+    options: &ListOptions,
+) -> io::Result<()> {
+    for record in records {
+        for (index, (_, value)) in record.0.iter().enumerate() {
+            if index > 0 {
+                // NOTE unescaped values may hold tabs, so only NUL can separate them unambiguously
+                writer.write_all(if options.null { b"\0" } else { b"\t" })?;
+            }
+
+            let text = value.text("");
+
+            if options.null {
+                writer.write_all(text.as_bytes())?;
+            } else {
+                for c in text.chars() {
+                    match c {
+                        '\\' => writer.write_all(b"\\\\")?,
+                        '\t' => writer.write_all(b"\\t")?,
+                        '\n' => writer.write_all(b"\\n")?,
+                        '\r' => writer.write_all(b"\\r")?,
+                        c => writer.write_all(c.encode_utf8(&mut [0; 4]).as_bytes())?,
+                    }
+                }
+            }
+        }
+
+        writer.write_all(if options.null { b"\0" } else { b"\n" })?;
+    }
+
+    writer.flush()
+}
+
+fn write_csv<W: Write>(
+    writer: &mut W,
+    header: &[String],
+    records: &[Record],
+    options: &ListOptions,
+) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    if !options.no_header {
+        writer.write_record(header)?;
+    }
+
+    for record in records {
+        writer.write_record(record.0.iter().map(|(_, value)| value.text("")))?;
+    }
+
+    writer.flush()
+}
+
+/// Write records in one of the structured formats, naming the TOML array of tables `table`.
+fn write_records<W: Write>(
+    writer: &mut W,
+    format: ListFormat,
+    table: &str,
+    records: &[Record],
+) -> io::Result<()> {
+    match format {
+        ListFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, records)?;
+            writeln!(writer)?;
+        }
+        ListFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut *writer, record)?;
+                writeln!(writer)?;
+            }
+        }
+        ListFormat::Yaml => {
+            if records.is_empty() {
+                writeln!(writer, "[]")?;
+            }
+
+            for record in records {
+                for (index, (key, value)) in record.0.iter().enumerate() {
+                    let indent = if index == 0 { "- " } else { "  " };
+                    let scalar = value.scalar();
+
+                    writeln!(
+                        writer,
+                        "{}{}: {}",
+                        indent,
+                        key,
+                        scalar.as_deref().unwrap_or("null")
+                    )?;
+                }
+            }
+        }
+        ListFormat::Toml => {
+            for (index, record) in records.iter().enumerate() {
+                if index > 0 {
+                    writeln!(writer)?;
+                }
+
+                writeln!(writer, "[[{}]]", table)?;
+
+                // NOTE TOML has no null, so missing values are left out
+                for (key, value) in record.0.iter() {
+                    if let Some(scalar) = value.scalar() {
+                        writeln!(writer, "{} = {}", key, scalar)?;
+                    }
+                }
+            }
+        }
+        format => unreachable!("{} is not a structured format", format),
+    }
+
+    writer.flush()
+}
+
+fn write_profiles_tree<W: Write>(
+    writer: &mut W,
+    rows: &[Row],
+    options: &ListOptions,
+) -> io::Result<()> {
+    let mut files: IndexMap<&Path, Vec<&Row>> = IndexMap::new();
+
+    for row in rows {
+        files.entry(&row.profile.file.path).or_default().push(row);
+    }
+
+    // NOTE names, URIs and files are implied by the tree itself, so only the other columns are shown
+    let columns: Vec<Column> = options
+        .columns
+        .iter()
+        .copied()
+        .filter(|c| !matches!(c, Column::Name | Column::Uri | Column::File))
+        .collect();
+
+    for (_, rows) in files {
+        let file = &rows[0].profile.file;
+
+        write!(
+            writer,
+            "{}/ {}",
+            file.prefix().unwrap_or_default(),
+            file.home_path().display()
+        )?;
+
+        if let Some(encryption) = file.encryption() {
+            write!(writer, " [{}]", encryption)?;
+        }
+
+        writeln!(writer)?;
+
+        for (index, row) in rows.iter().enumerate() {
+            write!(writer, "{} {}", branch(index, rows.len()), row.profile.name)?;
+
+            for column in columns.iter() {
+                match row.value(*column) {
+                    Value::Empty => {}
+                    value => write!(writer, " {}={}", column, value.text(""))?,
+                }
+            }
+
+            writeln!(writer)?;
+        }
+    }
+
+    writer.flush()
+}
+
+fn write_conflicts_tree<W: Write>(writer: &mut W, conflicts: &[Conflict]) -> io::Result<()> {
+    for conflict in conflicts.iter() {
+        writeln!(writer, "{}", conflict.name)?;
+        writeln!(
+            writer,
+            "├── {} {} (wins)",
+            conflict.winner.uri(),
+            conflict.winner.file.home_path().display()
+        )?;
+
+        for (index, profile) in conflict.shadowed.iter().enumerate() {
+            writeln!(
+                writer,
+                "{} {} {}",
+                branch(index, conflict.shadowed.len()),
+                profile.uri(),
+                profile.file.home_path().display()
+            )?;
+        }
+    }
+
+    writer.flush()
+}
+
+fn branch(index: usize, len: usize) -> &'static str {
+    if index + 1 == len {
+        "└──"
+    } else {
+        "├──"
+    }
+}
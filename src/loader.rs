@@ -1,13 +1,16 @@
-mod credentials_file;
+mod aws_config;
+pub(crate) mod credentials_file;
 pub(crate) mod profile;
 
+pub use aws_config::AwsConfigFile;
 pub use credentials_file::AwsCredentialsFile;
-pub use profile::AwsProfile;
+pub use profile::{AwsProfile, KeyType};
 
 use crate::utils;
 
 use indexmap::IndexMap;
 use std::collections::BTreeSet;
+use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -16,7 +19,10 @@ use tokio::fs;
 use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
 use tokio_stream::wrappers::ReadDirStream;
-use tokio_stream::{self as stream, StreamExt};
+use tokio_stream::StreamExt;
+
+/// The extension appended to the names of backed up files, which are ignored when loading profiles.
+pub(crate) const BACKUP_EXTENSION: &str = "bak";
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FileSource {
@@ -36,24 +42,28 @@ impl FileSource {
         if self.is_root() {
             None
         } else {
-            if let Some(stem) = self.path.file_stem() {
-                Some(stem.to_string_lossy().to_string())
-            } else {
-                None
-            }
+            self.path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
         }
     }
 
     pub fn from_path<P: AsRef<Path>>(p: P) -> Self {
         Self {
             path: p.as_ref().into(),
-            encrypted: if let Some(extension) = p.as_ref().extension() {
-                let extension = extension.to_str().expect("path is not valid utf-8");
+            encrypted: matches!(
+                p.as_ref().extension().and_then(OsStr::to_str),
+                Some("asc") | Some("gpg") | Some("pgp")
+            ),
+        }
+    }
 
-                extension.eq("asc") || extension.eq("gpg") || extension.eq("pgp")
-            } else {
-                false
-            },
+    /// How the file is encrypted, going by its extension, or `None` for plaintext files.
+    pub fn encryption(&self) -> Option<&'static str> {
+        match self.path.extension().and_then(|e| e.to_str()) {
+            Some("asc") => Some("gpg-armored"),
+            Some("gpg") | Some("pgp") => Some("gpg"),
+            _ => None,
         }
     }
 
@@ -72,7 +82,9 @@ pub struct AwsCredentials {
 }
 
 impl AwsCredentials {
-    pub async fn load_all() -> Result<Self, Box<dyn std::error::Error>> {
+    /// Find the credential files `load_all` reads, `~/.aws/credentials.d/*` and `~/.aws/credentials`, without
+    /// reading or decrypting any of them.
+    pub async fn list_files() -> Result<Vec<FileSource>, Box<dyn std::error::Error>> {
         let home = dirs::home_dir().ok_or("unable to get user's home directory")?;
         let aws_config_dir = home.join(".aws");
 
@@ -88,9 +100,6 @@ impl AwsCredentials {
 
         // NOTE we _could_ abort here if !creds_d.is_dir(), but we are chaining on ~/.aws/credentials
 
-        let (plain_sync, encrypted_sync) =
-            (Arc::new(Semaphore::new(32)), Arc::new(Semaphore::new(4)));
-
         let creds_files: Vec<PathBuf> = match fs::read_dir(&creds_d).await {
             Ok(creds) => {
                 ReadDirStream::new(creds)
@@ -105,32 +114,38 @@ impl AwsCredentials {
             }
         };
 
-        let handles = stream::iter(creds_files)
-            .chain(stream::iter(vec![aws_config_dir.join("credentials")]))
+        Ok(creds_files
+            .into_iter()
+            .chain(std::iter::once(aws_config_dir.join("credentials")))
             .filter(|p| p.is_file())
-            .map(|p| {
+            // skip backups left by editing commands, which would otherwise shadow the files they back up
+            .filter(|p| p.extension().is_none_or(|e| e != BACKUP_EXTENSION))
+            .map(FileSource::from_path)
+            .collect())
+    }
+
+    pub async fn load_all() -> Result<Self, Box<dyn std::error::Error>> {
+        let (plain_sync, encrypted_sync) =
+            (Arc::new(Semaphore::new(32)), Arc::new(Semaphore::new(4)));
+
+        // NOTE spawning from a plain iterator keeps this future `Send`, so it can be driven from spawned tasks
+        let handles = Self::list_files()
+            .await?
+            .into_iter()
+            .map(|source| {
                 let (plain_permit, encrypted_permit) = (plain_sync.clone(), encrypted_sync.clone());
 
-                tokio::spawn(async {
-                    let name = p.file_name().unwrap().to_string_lossy();
-
-                    if name.ends_with(".asc") || name.ends_with(".gpg") || name.ends_with(".pgp") {
-                        Some({
-                            let work = encrypted_permit.acquire_owned().await.unwrap();
-                            let r = AwsCredentialsFile::load_encrypted(p).await;
-                            drop(work);
-                            r
-                        })
-                    } else if name.ends_with(".ini") || name.ends_with("") {
-                        Some({
-                            let work = plain_permit.acquire_owned().await.unwrap();
-                            let r = AwsCredentialsFile::load(p).await;
-                            drop(work);
-                            r
-                        })
+                tokio::spawn(async move {
+                    if source.encrypted {
+                        let work = encrypted_permit.acquire_owned().await.unwrap();
+                        let r = AwsCredentialsFile::load_encrypted(source.path).await;
+                        drop(work);
+                        r
                     } else {
-                        log::debug!("Skipping file with unknown extension {}", p.display());
-                        None
+                        let work = plain_permit.acquire_owned().await.unwrap();
+                        let r = AwsCredentialsFile::load(source.path).await;
+                        drop(work);
+                        r
                     }
                 })
             })
@@ -138,18 +153,16 @@ impl AwsCredentials {
 
         let mut credentials = BTreeSet::new();
 
-        for h in handles.await {
+        for h in handles {
             let h = h.await.unwrap();
 
-            if let Some(t) = h {
-                if t.is_err() {
-                    log::warn!("{}", t.unwrap_err());
-                } else {
-                    let f = t.unwrap();
+            match h {
+                Ok(f) => {
                     log::info!("Loaded: {}", utils::strip_homedir(&f.file.path).display());
 
                     credentials.insert(f);
                 }
+                Err(e) => log::warn!("{}", e),
             }
         }
 
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests;
+
+use super::{AwsProfile, Error};
+
+use crate::ini::{self, IniValue};
+use crate::utils;
+
+use indexmap::IndexMap;
+
+use std::io;
+use std::path::PathBuf;
+
+/// The environment variable overriding the location of the AWS CLI's configuration file.
+pub const AWS_CONFIG_FILE_ENV_VAR: &str = "AWS_CONFIG_FILE";
+
+const INI_PROFILE_SECTION_PREFIX: &str = "profile ";
+const INI_DEFAULT_SECTION: &str = "default";
+const INI_REGION_TAG: &str = "region";
+
+/// Non-secret settings from the AWS CLI's configuration file, `~/.aws/config` unless overridden.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AwsConfigFile {
+    /// The region configured for each profile name.
+    pub regions: IndexMap<String, String>,
+}
+
+impl AwsConfigFile {
+    /// The path of the configuration file, taken from `$AWS_CONFIG_FILE` if set.
+    pub fn path() -> PathBuf {
+        std::env::var_os(AWS_CONFIG_FILE_ENV_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| utils::homedir().join(".aws").join("config"))
+    }
+
+    /// Load the configuration file, which is empty if it doesn't exist.
+    pub async fn load() -> Result<Self, Error> {
+        let path = Self::path();
+
+        match tokio::fs::read_to_string(&path).await {
+            Ok(source) => Ok(Self::parse(&source)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(format!("unable to read {}: {}", path.display(), e).into()),
+        }
+    }
+
+    /// Parse a configuration file, where profiles are defined in `[profile NAME]` sections, except for `[default]`.
+    pub fn parse(source: &str) -> Self {
+        let mut config = Self::default();
+
+        for (section, properties) in ini::parse(source) {
+            let name = match section.strip_prefix(INI_PROFILE_SECTION_PREFIX) {
+                Some(name) => name.trim(),
+                None if section == INI_DEFAULT_SECTION => section,
+                None => continue,
+            };
+
+            if let Some(region) = properties.get(INI_REGION_TAG).and_then(IniValue::as_str) {
+                config.regions.insert(name.to_string(), region.to_string());
+            }
+        }
+
+        config
+    }
+
+    /// The region of the given profile, preferring one configured alongside its credentials.
+    pub fn region<'a>(&'a self, profile: &'a AwsProfile) -> Option<&'a str> {
+        profile
+            .region
+            .as_deref()
+            .or_else(|| self.regions.get(&profile.name).map(|r| r.as_str()))
+    }
+}
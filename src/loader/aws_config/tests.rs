@@ -0,0 +1,45 @@
+use super::AwsConfigFile;
+use crate::loader::FileSource;
+use crate::AwsProfile;
+
+const CONFIG: &str = r#"
+[default]
+region = us-east-1
+output = json
+
+[profile prod]
+region = eu-west-1
+
+[profile dev]
+output = table
+
+[sso-session work]
+region = eu-central-1
+"#;
+
+#[test]
+fn test_regions() {
+    let config = AwsConfigFile::parse(CONFIG);
+
+    assert_eq!(2, config.regions.len());
+    assert_eq!(
+        Some("us-east-1"),
+        config.regions.get("default").map(|r| r.as_str())
+    );
+    assert_eq!(
+        Some("eu-west-1"),
+        config.regions.get("prod").map(|r| r.as_str())
+    );
+
+    let file = FileSource::from_path("/tmp/credentials");
+    let mut prod = AwsProfile::new("prod", "AKIAEXAMPLE", String::new(), None, file.clone());
+    let dev = AwsProfile::new("dev", "AKIAEXAMPLE", String::new(), None, file);
+
+    assert_eq!(Some("eu-west-1"), config.region(&prod));
+    assert_eq!(None, config.region(&dev));
+
+    // regions configured alongside credentials win
+    prod.region = Some("ap-south-1".into());
+
+    assert_eq!(Some("ap-south-1"), config.region(&prod));
+}
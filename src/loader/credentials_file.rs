@@ -3,7 +3,7 @@ mod tests;
 
 use super::{AwsProfile, Error, FileSource, ProfilesParsed};
 
-use crate::ini;
+use crate::ini::{self, IniValue};
 use crate::utils;
 
 use indexmap::IndexMap;
@@ -20,9 +20,12 @@ use tokio::process::Command;
 
 use zeroize::Zeroizing;
 
-const INI_ACCESS_KEY_ID_TAG: &'static str = "aws_access_key_id";
-const INI_SECRET_ACCESS_KEY_TAG: &'static str = "aws_secret_access_key";
-const INI_SESSION_TOKEN_TAG: &'static str = "aws_session_token";
+pub(crate) const INI_ACCESS_KEY_ID_TAG: &str = "aws_access_key_id";
+pub(crate) const INI_SECRET_ACCESS_KEY_TAG: &str = "aws_secret_access_key";
+pub(crate) const INI_SESSION_TOKEN_TAG: &str = "aws_session_token";
+pub(crate) const INI_REGION_TAG: &str = "region";
+/// Keys used by various credential helpers to record when temporary credentials expire.
+pub(crate) const INI_EXPIRATION_TAGS: &[&str] = &["aws_expiration", "x_security_token_expires"];
 
 pub struct AwsCredentialsFile {
     pub profiles: IndexMap<String, AwsProfile>,
@@ -104,8 +107,7 @@ impl Display for AwsCredentialsFile {
             profiles = self
                 .profiles
                 .keys()
-                .into_iter()
-                .map(|k| k.clone())
+                .cloned()
                 .collect::<Vec<String>>()
                 .join(", ")
         )
@@ -119,20 +121,30 @@ impl Debug for AwsCredentialsFile {
 }
 
 impl AwsCredentialsFile {
-    fn read_profiles<P: AsRef<Path>>(data: String, path: P) -> Result<ProfilesParsed, Error> {
-        // when data is dropped, it will be securely erased
-        let data = Zeroizing::new(data);
-
-        // owns no values except the containers, all strings are references to sections of `data`
-        let config = ini::parse(data.as_str());
+    fn read_profiles<P: AsRef<Path>>(
+        data: Zeroizing<String>,
+        path: P,
+    ) -> Result<ProfilesParsed, Error> {
+        // owns little besides the containers, values are references to sections of `data` unless they span lines
+        let (config, diagnostics) = ini::parse_with_diagnostics(data.as_str());
+
+        for diagnostic in diagnostics {
+            log::warn!(
+                "{}:{}",
+                utils::strip_homedir(path.as_ref()).display(),
+                diagnostic
+            );
+        }
 
         // storage for the profiles we have loaded
         let mut profiles = IndexMap::with_capacity(config.len());
 
         for (section_name, section) in config {
-            if let (Some(Some(access_key_id)), Some(Some(secret_access_key))) = (
-                section.get(INI_ACCESS_KEY_ID_TAG),
-                section.get(INI_SECRET_ACCESS_KEY_TAG),
+            let value = |tag| section.get(tag).and_then(IniValue::as_str);
+
+            if let (Some(access_key_id), Some(secret_access_key)) = (
+                value(INI_ACCESS_KEY_ID_TAG),
+                value(INI_SECRET_ACCESS_KEY_TAG),
             ) {
                 // if it has both an access key id and a secret access key, add it
                 profiles.insert(
@@ -142,13 +154,24 @@ impl AwsCredentialsFile {
                         access_key_id: access_key_id.to_string(),
                         secret_access_key: Zeroizing::from(secret_access_key.to_string()),
                         // optionally add the session token if present
-                        session_token: if let Some(Some(session_token)) =
-                            section.get(INI_SESSION_TOKEN_TAG)
-                        {
-                            Some(Zeroizing::from(session_token.to_string()))
-                        } else {
-                            None
-                        },
+                        session_token: value(INI_SESSION_TOKEN_TAG)
+                            .map(|session_token| Zeroizing::from(session_token.to_string())),
+                        region: value(INI_REGION_TAG).map(str::to_string),
+                        expiration: INI_EXPIRATION_TAGS
+                            .iter()
+                            .find_map(|tag| value(tag))
+                            .and_then(|value| match humantime::parse_rfc3339_weak(value) {
+                                Ok(expiration) => Some(expiration),
+                                Err(e) => {
+                                    log::warn!(
+                                        "Ignoring invalid expiration '{}' of profile {}: {}",
+                                        value,
+                                        section_name,
+                                        e
+                                    );
+                                    None
+                                }
+                            }),
                         file: FileSource {
                             path: path.as_ref().to_path_buf(),
                             encrypted: false,
@@ -158,7 +181,7 @@ impl AwsCredentialsFile {
             }
         }
 
-        if profiles.len() > 0 {
+        if !profiles.is_empty() {
             Ok(profiles)
         } else {
             Err("file contains no profiles".into())
@@ -171,7 +194,8 @@ impl AwsCredentialsFile {
         file.read_to_string(&mut storage).await?;
 
         // load the INI from the string in memory
-        let result = Self::read_profiles(storage, p.as_ref()).map_err(|e| {
+        // when storage is dropped, it will be securely erased
+        let result = Self::read_profiles(Zeroizing::new(storage), p.as_ref()).map_err(|e| {
             Into::<Error>::into(format!(
                 "Unable to read profiles from {}: {}",
                 utils::strip_homedir(p.as_ref()).display(),
@@ -189,65 +213,78 @@ impl AwsCredentialsFile {
     }
 
     pub async fn load_encrypted<P: AsRef<Path>>(p: P) -> Result<Self, Error> {
-        // gpg --batch -d {file}
-        let mut decrypt = Command::new("gpg")
-            .arg("--batch")
-            .arg("-d")
-            .arg(p.as_ref().to_str().expect("invalid utf-8 in filename"))
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap();
-
-        let rc = decrypt.wait().await?;
-
-        if !rc.success() {
-            let stderr = decrypt.stderr;
-
-            Err(format!(
-                "Unable to decrypt file with gpg (rc={rc}): {p}{stderr}",
-                p = p.as_ref().display(),
-                rc = rc.code().unwrap(),
-                stderr = if let Some(mut stderr) = stderr {
-                    let mut output = String::new();
-
-                    let _ = stderr.read_to_string(&mut output).await.unwrap();
-
-                    format!(
-                        "\n{}",
-                        output
-                            .lines()
-                            .map(|line| format!("    {}", line))
-                            .collect::<Vec<String>>()
-                            .join("\n")
-                    )
-                } else {
-                    "".to_string()
-                }
-            )
-            .into())
-        } else {
-            let mut storage = String::new();
-
-            if let Ok(_) = decrypt.stdout.unwrap().read_to_string(&mut storage).await {
-                let result = Self::read_profiles(storage, p.as_ref()).map_err(|e| {
-                    Into::<Error>::into(format!(
-                        "Unable to read profiles from encrypted file {}: {}",
-                        p.as_ref().display(),
-                        e
-                    ))
-                })?;
-
-                Ok(AwsCredentialsFile {
-                    file: FileSource {
-                        encrypted: true,
-                        path: p.as_ref().to_path_buf(),
-                    },
-                    profiles: result,
-                })
+        let storage = decrypt(p.as_ref()).await?;
+
+        let result = Self::read_profiles(storage, p.as_ref()).map_err(|e| {
+            Into::<Error>::into(format!(
+                "Unable to read profiles from encrypted file {}: {}",
+                p.as_ref().display(),
+                e
+            ))
+        })?;
+
+        Ok(AwsCredentialsFile {
+            file: FileSource {
+                encrypted: true,
+                path: p.as_ref().to_path_buf(),
+            },
+            profiles: result,
+        })
+    }
+}
+
+/// Decrypt a file with gpg into memory, which is securely erased once dropped.
+pub(crate) async fn decrypt<P: AsRef<Path>>(p: P) -> Result<Zeroizing<String>, Error> {
+    // gpg --batch -d {file}
+    let mut decrypt = Command::new("gpg")
+        .arg("--batch")
+        .arg("-d")
+        .arg(p.as_ref().to_str().expect("invalid utf-8 in filename"))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let rc = decrypt.wait().await?;
+
+    if !rc.success() {
+        let stderr = decrypt.stderr;
+
+        Err(format!(
+            "Unable to decrypt file with gpg (rc={rc}): {p}{stderr}",
+            p = p.as_ref().display(),
+            rc = rc.code().unwrap(),
+            stderr = if let Some(mut stderr) = stderr {
+                let mut output = String::new();
+
+                let _ = stderr.read_to_string(&mut output).await.unwrap();
+
+                format!(
+                    "\n{}",
+                    output
+                        .lines()
+                        .map(|line| format!("    {}", line))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                )
             } else {
-                Err(format!("unable to read gpg stdout").into())
+                "".to_string()
             }
+        )
+        .into())
+    } else {
+        let mut storage = Zeroizing::new(String::new());
+
+        if decrypt
+            .stdout
+            .unwrap()
+            .read_to_string(&mut storage)
+            .await
+            .is_ok()
+        {
+            Ok(storage)
+        } else {
+            Err("unable to read gpg stdout".into())
         }
     }
 }
@@ -1,6 +1,7 @@
 use super::{AwsCredentialsFile, FileSource};
 
 use std::collections::BTreeSet;
+use std::time::{Duration, UNIX_EPOCH};
 
 #[test]
 fn test_ordering() {
@@ -111,3 +112,61 @@ fn test_encrypted_ordering() {
     assert_eq!(credentials_d.join("50-a"), current.file.path);
     assert!(current.file.encrypted);
 }
+
+#[test]
+fn test_read_optional_properties() {
+    let path = dirs::home_dir().unwrap().join(".aws").join("credentials");
+
+    let profiles = AwsCredentialsFile::read_profiles(
+        r#"
+[plain]
+aws_access_key_id = a
+aws_secret_access_key = b
+
+[temporary]
+aws_access_key_id = c
+aws_secret_access_key = d
+aws_session_token = e
+region = eu-west-1
+x_security_token_expires = 2027-01-15T08:00:00Z
+
+[invalid]
+aws_access_key_id = f
+aws_secret_access_key = g
+aws_expiration = tomorrow
+
+# read the way the AWS CLI reads it
+[cli]
+AWS_Access_Key_ID = h
+s3 =
+  max_concurrent_requests = 20
+aws_secret_access_key: i
+"#
+        .to_string()
+        .into(),
+        &path,
+    )
+    .unwrap();
+
+    let plain = profiles.get("plain").unwrap();
+
+    assert!(plain.session_token.is_none());
+    assert!(plain.region.is_none());
+    assert!(plain.expiration.is_none());
+
+    let temporary = profiles.get("temporary").unwrap();
+
+    assert_eq!("e", temporary.session_token.as_ref().unwrap().as_str());
+    assert_eq!(Some("eu-west-1"), temporary.region.as_deref());
+    assert_eq!(
+        Some(UNIX_EPOCH + Duration::from_secs(1_800_000_000)),
+        temporary.expiration
+    );
+
+    assert!(profiles.get("invalid").unwrap().expiration.is_none());
+
+    let cli = profiles.get("cli").unwrap();
+
+    assert_eq!("h", cli.access_key_id);
+    assert_eq!("i", cli.secret_access_key.as_str());
+}
@@ -4,17 +4,43 @@ mod tests;
 use crate::loader::FileSource;
 
 use std::fmt::{Debug, Display, Formatter};
+use std::time::SystemTime;
 
 use zeroize::Zeroizing;
 
+#[derive(Clone)]
 pub struct AwsProfile {
     pub name: String,
     pub access_key_id: String,
     pub secret_access_key: Zeroizing<String>,
     pub session_token: Option<Zeroizing<String>>,
+    /// The region configured alongside the credentials, if any.
+    pub region: Option<String>,
+    /// When temporary credentials expire, if known.
+    pub expiration: Option<SystemTime>,
     pub file: FileSource,
 }
 
+/// The kind of credentials an access key id belongs to, as told by its prefix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyType {
+    /// Long-term credentials of an IAM user, prefixed with `AKIA`.
+    LongTerm,
+    /// Temporary credentials issued by STS, prefixed with `ASIA`.
+    Temporary,
+    Unknown,
+}
+
+impl Display for KeyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            KeyType::LongTerm => "long-term",
+            KeyType::Temporary => "temporary",
+            KeyType::Unknown => "unknown",
+        })
+    }
+}
+
 impl AwsProfile {
     pub fn new<S: Into<String>>(
         name: S,
@@ -27,7 +53,9 @@ impl AwsProfile {
             name: name.into(),
             access_key_id: access_key_id.into(),
             secret_access_key: Zeroizing::new(secret_access_key),
-            session_token: session_token.map(|t| Zeroizing::new(t)),
+            session_token: session_token.map(Zeroizing::new),
+            region: None,
+            expiration: None,
             file,
         }
     }
@@ -40,6 +68,31 @@ impl AwsProfile {
         self.file.prefix()
     }
 
+    pub fn key_type(&self) -> KeyType {
+        if self.access_key_id.starts_with("AKIA") {
+            KeyType::LongTerm
+        } else if self.access_key_id.starts_with("ASIA") {
+            KeyType::Temporary
+        } else {
+            KeyType::Unknown
+        }
+    }
+
+    /// The access key id with all but its first and last four characters elided, e.g. `AKIA…WXYZ`.
+    ///
+    /// Only the first four characters are kept for access key ids too short to elide anything in between.
+    pub fn masked_access_key_id(&self) -> String {
+        let chars: Vec<char> = self.access_key_id.chars().collect();
+        let head: String = chars.iter().take(4).collect();
+
+        if chars.len() < 12 {
+            format!("{}…", head)
+        } else {
+            let tail: String = chars[chars.len() - 4..].iter().collect();
+            format!("{}…{}", head, tail)
+        }
+    }
+
     pub fn uri(&self) -> String {
         if let Some(prefix) = self.prefix() {
             format!("{prefix}/{name}", prefix = prefix, name = self.name)
@@ -1,4 +1,21 @@
-use aws_env::{AwsCredentials, AwsCredentialsFile, AwsProfile, AwsProfileLookup};
+use aws_env::agent::{self, Agent, AgentClient};
+use aws_env::ecs::{self, Ecs};
+use aws_env::edit::{self, Credentials, EditOptions, ProfileExists};
+use aws_env::export::{self, ExportFormat, ExportOptions, Template};
+use aws_env::imds::Imds;
+use aws_env::lint::{self, Severity};
+use aws_env::list::{
+    self, Column, ListFormat, ListOptions, DEFAULT_LIST_COLUMNS, LIST_COLUMNS, LIST_FORMATS,
+};
+use aws_env::query::{ProfileQuery, SortKey, SORT_KEYS};
+use aws_env::shell;
+use aws_env::{
+    AmbiguousProfile, AwsConfigFile, AwsCredentials, AwsCredentialsFile, AwsProfile,
+    AwsProfileLookup, FileSource, ProfileNotFound,
+};
+
+use dialoguer::console::Term;
+use dialoguer::{FuzzySelect, Password};
 
 use log::LevelFilter;
 
@@ -7,101 +24,292 @@ use log4rs::append::console::{ConsoleAppender, Target};
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::Config;
-use prettytable::format::{LinePosition, LineSeparator};
-
-use prettytable::{cell, format, row, Table};
-
-use serde::Serialize;
 
+use std::fmt::{Display, Formatter};
+use std::fs::File;
 use std::io;
-use std::io::Write;
-use std::io::{BufWriter, LineWriter};
-use std::process::exit;
-use std::str::FromStr;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::io::{IsTerminal, Read, Write};
+use std::net::{Ipv6Addr, SocketAddr};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
 
 use structopt::StructOpt;
 
-use tokio;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::OnceCell;
 
-const LIST_FORMATS: &'static [&'static str] = &["table", "plain", "csv", "json"];
-const LOG_LEVELS: &'static [&'static str] = &["trace", "debug", "info", "warn", "error"];
+use zeroize::Zeroizing;
+
+const LOG_LEVELS: &[&str] = &["trace", "debug", "info", "warn", "error"];
+
+/// Whether `--strict` was given, refusing ambiguous bare profile names.
+static STRICT: AtomicBool = AtomicBool::new(false);
+/// The configuration file once loaded, see `config`.
+static CONFIG: OnceCell<Result<aws_env::config::Config, String>> = OnceCell::const_new();
 
 #[derive(Debug, StructOpt)]
 struct CliArgs {
     /// Set the logging level for the utility.
     #[structopt(long="log-level", default_value="error", possible_values=LOG_LEVELS)]
     log_level: String,
+    /// Refuse bare profile names defined in more than one file instead of picking the highest priority profile. This
+    /// can also be enabled with `strict = true` in the [settings] section of the configuration file.
+    #[structopt(long = "strict")]
+    strict: bool,
     #[structopt(subcommand)]
     cmd: CliCommand,
 }
 
 #[derive(Debug, StructOpt)]
 enum CliCommand {
+    /// Add a profile to a credentials file, prompting for its credentials or reading them from standard input.
+    Add(AddCommand),
+    /// Run an agent which caches decrypted profiles in memory.
+    Agent(AgentCommand),
+    /// Show which known profile the credentials in the environment belong to.
+    Current(CurrentCommand),
     /// Export the specified profile.
     Export(ExportCommand),
+    /// Check credential files for problems, such as malformed lines, duplicates and incomplete profiles.
+    Lint(LintCommand),
     /// List available profiles.
     List(ListCommand),
+    /// Remove a profile from its credentials file.
+    Remove(RemoveCommand),
+    /// Rename a profile within its credentials file.
+    Rename(RenameCommand),
+    /// Serve the specified profile via an emulated EC2 instance metadata service (IMDSv2).
+    ServeImds(ServeImdsCommand),
+    /// Serve the specified profile via an emulated ECS container credentials endpoint.
+    ServeEcs(ServeEcsCommand),
+    /// Start an interactive shell with the specified profile's credentials.
+    Shell(ShellCommand),
+}
+
+#[derive(Debug, StructOpt)]
+struct AddCommand {
+    /// The profile to add, as `prefix/name` for the file in ~/.aws/credentials.d with that prefix, created as
+    /// `prefix.ini` if there is none, or as `/name` for ~/.aws/credentials.
+    ///
+    /// Credentials are prompted for without echo on a terminal. Otherwise they're read from standard input, either as
+    /// the JSON output of `aws sts` commands, `aws iam create-access-key` or a credential process, or as lines holding
+    /// the access key id, the secret access key and optionally a session token.
+    #[structopt(name = "uri")]
+    uri: String,
+    /// Replace the profile if it already exists.
+    #[structopt(short = "f", long = "force")]
+    force: bool,
+    /// The region to set for the profile.
+    #[structopt(long = "region")]
+    region: Option<String>,
+    /// Encrypt the file to the given gpg recipient rather than to the recipients it is already encrypted to. A new
+    /// file is created as `prefix.gpg` when recipients are given. Can be given more than once. Only gpg is supported,
+    /// files encrypted with age can't be edited.
+    #[structopt(short = "r", long = "recipient", number_of_values = 1)]
+    recipients: Vec<String>,
+    /// Back up the file to `<file>.bak` before changing it.
+    #[structopt(short = "b", long = "backup")]
+    backup: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct RemoveCommand {
+    /// The profile to remove, as `prefix/name` or `/name`. See the 'list' command for URI format.
+    #[structopt(name = "uri")]
+    uri: String,
+    /// Back up the file to `<file>.bak` before changing it.
+    #[structopt(short = "b", long = "backup")]
+    backup: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct RenameCommand {
+    /// The profile to rename, as `prefix/name` or `/name`. See the 'list' command for URI format.
+    #[structopt(name = "uri")]
+    uri: String,
+    /// The new name of the profile, which stays in the same file.
+    #[structopt(name = "new_name")]
+    new_name: String,
+    /// Replace a profile by the new name if it already exists.
+    #[structopt(short = "f", long = "force")]
+    force: bool,
+    /// Back up the file to `<file>.bak` before changing it.
+    #[structopt(short = "b", long = "backup")]
+    backup: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct LintCommand {
+    /// The files to check, rather than every credential file.
+    #[structopt(name = "file", parse(from_os_str))]
+    files: Vec<PathBuf>,
+    /// Skip encrypted files rather than decrypting them.
+    #[structopt(long = "skip-encrypted")]
+    skip_encrypted: bool,
 }
 
 #[derive(Debug, StructOpt)]
 struct ListCommand {
-    /// Exclude the header when printing to a TTY.
+    /// Exclude the column titles from table and CSV output. The other formats have no header to exclude.
     #[structopt(long = "no-header")]
     no_header: bool,
+    /// Separate plain fields and records with NUL rather than tabs and newlines, e.g. for `xargs -0`, writing values
+    /// unescaped.
+    #[structopt(short = "0", long = "null")]
+    null: bool,
     /// The output format.
     #[structopt(short = "F", long = "format", default_value="table", possible_values=LIST_FORMATS)]
     format: ListFormat,
+    /// Only list profiles shadowed by a profile of the same name in a higher priority file, and which profile wins.
+    #[structopt(long = "conflicts", conflicts_with_all = &["effective", "shadowed"])]
+    conflicts: bool,
+    /// Only list the profiles bare names resolve to, hiding those shadowed by a higher priority file.
+    #[structopt(long = "effective", conflicts_with = "shadowed")]
+    effective: bool,
+    /// Only list profiles shadowed by a profile of the same name in a higher priority file.
+    #[structopt(long = "shadowed")]
+    shadowed: bool,
+    /// Only list profiles in files with the given prefix, e.g. `work` for ~/.aws/credentials.d/work.ini.
+    #[structopt(long = "prefix")]
+    prefix: Option<String>,
+    /// Only list profiles whose name or URI matches the given glob, e.g. `prod-*`.
+    #[structopt(long = "glob", conflicts_with = "regex")]
+    glob: Option<String>,
+    /// Only list profiles whose name or URI contains a match for the given regular expression.
+    #[structopt(long = "regex")]
+    regex: Option<String>,
+    /// Only list profiles from encrypted files.
+    #[structopt(long = "encrypted", conflicts_with = "plaintext")]
+    encrypted: bool,
+    /// Only list profiles from plaintext files.
+    #[structopt(long = "plaintext")]
+    plaintext: bool,
+    /// Only list profiles in ~/.aws/credentials.
+    #[structopt(long = "root-only")]
+    root_only: bool,
+    /// Only list profiles with a session token.
+    #[structopt(long = "has-session-token")]
+    has_session_token: bool,
+    /// What to sort profiles by, ties being kept in priority order.
+    #[structopt(long = "sort", default_value = "priority", possible_values = SORT_KEYS)]
+    sort: SortKey,
+    /// The comma-separated columns to list. Secrets are never listed, access key ids being masked, and the region
+    /// falls back to the one configured for the profile name in ~/.aws/config.
+    #[structopt(long = "columns", use_delimiter = true, default_value = DEFAULT_LIST_COLUMNS, possible_values = LIST_COLUMNS)]
+    columns: Vec<Column>,
+}
+
+#[derive(Debug, StructOpt)]
+struct AgentCommand {
+    /// The path of the agent's Unix socket. Defaults to a private directory under $XDG_RUNTIME_DIR or /tmp.
+    #[structopt(short = "a", long = "socket", parse(from_os_str))]
+    socket: Option<PathBuf>,
+    /// How long, in seconds, to keep decrypted profiles in memory before decrypting them again.
+    #[structopt(short = "t", long = "ttl", default_value = "3600")]
+    ttl: u64,
+    /// Stay in the foreground rather than detaching from the terminal.
+    #[structopt(short = "D", long = "foreground")]
+    foreground: bool,
+    /// Stop the agent referenced by $AWS_ENV_AGENT_SOCK.
+    #[structopt(short = "k", long = "kill", conflicts_with_all = &["socket", "foreground"])]
+    kill: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct CurrentCommand {
+    /// Also show the file the profile was read from and when its credentials expire, separated by tabs.
+    #[structopt(short = "l", long = "long")]
+    long: bool,
 }
 
 #[derive(Debug, StructOpt)]
 struct ExportCommand {
     /// The profile name to export. This can be either the bare profile name or a URI. See the 'list' command for URI format.
+    /// If omitted on a terminal, a profile can be picked interactively.
     #[structopt(name = "profile_name")]
-    name: String,
-}
-
-/// Output format for listing profiles.
-#[derive(Debug)]
-enum ListFormat {
-    /// Default table output format.
-    Table,
-    /// Plaintext format, useful for parsing with command-line tools.
-    Plain,
-    /// CSV format.
-    Csv,
-    /// JSON format.
-    Json,
+    name: Option<String>,
+    /// Export several profiles at once as ALIAS=PROFILE pairs, prefixing each profile's variables with its alias,
+    /// e.g. `--multi src=work/prod dst=client/prod` for `SRC_AWS_ACCESS_KEY_ID` and `DST_AWS_ACCESS_KEY_ID`.
+    #[structopt(long = "multi", min_values = 1, conflicts_with = "profile_name")]
+    multi: Vec<String>,
+    /// The output format. gitlab-ci writes a dotenv report with --to-file, whose variables GitLab can't mask; use
+    /// masked variables in the project's CI/CD settings for credentials which must stay masked.
+    #[structopt(short = "F", long = "format", default_value = "shell", possible_values = export::EXPORT_FORMATS)]
+    format: ExportFormat,
+    /// Export using a template defined in the configuration file instead of a built-in format.
+    #[structopt(short = "t", long = "template", conflicts_with = "format")]
+    template: Option<String>,
+    /// Write to the given file descriptor instead of standard output, e.g. `--fd 3` with `3>file`.
+    #[structopt(long = "fd", conflicts_with = "to-file")]
+    fd: Option<RawFd>,
+    /// Write to the given file instead of standard output. The file is atomically created or replaced with mode 0600,
+    /// refusing to follow symbolic links or to overwrite files accessible by other users.
+    #[structopt(long = "to-file", parse(from_os_str))]
+    to_file: Option<PathBuf>,
+    /// The name of the secret for the k8s-secret format.
+    #[structopt(long = "secret-name", default_value = export::DEFAULT_SECRET_NAME)]
+    secret_name: String,
+    /// The namespace of the secret for the k8s-secret format. If omitted, kubectl's current namespace is used.
+    #[structopt(short = "n", long = "namespace")]
+    namespace: Option<String>,
+    /// Also export AWS_ENV_PROFILE, AWS_ENV_SOURCE and AWS_CREDENTIAL_EXPIRATION describing the profile, e.g. for
+    /// shell prompts. Only the environment variable formats and github-actions support this.
+    #[structopt(short = "m", long = "metadata")]
+    metadata: bool,
 }
 
-impl Default for ListFormat {
-    fn default() -> Self {
-        ListFormat::Table
-    }
+#[derive(Debug, StructOpt)]
+struct ServeImdsCommand {
+    /// The profile name to serve. This can be either the bare profile name or a URI.
+    #[structopt(name = "profile_name")]
+    name: String,
+    /// The address to listen on.
+    #[structopt(short = "l", long = "listen", default_value = "127.0.0.1:8169")]
+    listen: SocketAddr,
+    /// The name of the instance role to report the credentials under.
+    #[structopt(short = "r", long = "role", default_value = "aws-env")]
+    role: String,
+    /// The lifetime, in seconds, advertised to clients in the credentials' expiration.
+    #[structopt(long = "lifetime", default_value = "3600")]
+    lifetime: u64,
 }
 
-impl ToString for ListFormat {
-    fn to_string(&self) -> String {
-        match *self {
-            ListFormat::Table => "table".into(),
-            ListFormat::Plain => "plain".into(),
-            ListFormat::Csv => "csv".into(),
-            ListFormat::Json => "json".into(),
-        }
-    }
+#[derive(Debug, StructOpt)]
+struct ServeEcsCommand {
+    /// The profile name to serve. This can be either the bare profile name or a URI.
+    #[structopt(name = "profile_name")]
+    name: String,
+    /// The address to listen on.
+    #[structopt(short = "l", long = "listen", default_value = "127.0.0.1:8170")]
+    listen: SocketAddr,
+    /// The host name containers use to reach the endpoint. Defaults to the listening address.
+    #[structopt(short = "H", long = "host")]
+    host: Option<String>,
+    /// The lifetime, in seconds, advertised to clients in the credentials' expiration.
+    #[structopt(long = "lifetime", default_value = "3600")]
+    lifetime: u64,
 }
 
-impl FromStr for ListFormat {
-    type Err = Box<dyn std::error::Error>;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.trim().to_lowercase().as_str() {
-            "table" => Ok(ListFormat::Table),
-            "plain" => Ok(ListFormat::Plain),
-            "csv" => Ok(ListFormat::Csv),
-            "json" => Ok(ListFormat::Json),
-            _ => Err(format!("unknown format {}", s).into()),
-        }
-    }
+#[derive(Debug, StructOpt)]
+struct ShellCommand {
+    /// The profile name to use. This can be either the bare profile name or a URI. If omitted on a terminal, a profile
+    /// can be picked interactively.
+    #[structopt(name = "profile_name")]
+    name: Option<String>,
+    /// Start the shell even when already inside another aws-env shell.
+    #[structopt(short = "f", long = "force")]
+    force: bool,
+    /// Prefix the shell's prompt ($PS1) with the profile's URI. Most shells set their prompt in their startup files,
+    /// overriding this; use $AWS_ENV_SHELL in the prompt instead for those.
+    #[structopt(short = "p", long = "prompt")]
+    prompt: bool,
+    /// How long, in seconds, before temporary credentials expire to warn that they are about to.
+    #[structopt(long = "warn-before", default_value = "300")]
+    warn_before: u64,
 }
 
 fn main() {
@@ -131,124 +339,282 @@ async fn async_main() -> Result<(), Box<dyn std::error::Error>> {
         _ => LevelFilter::Error,
     });
 
+    STRICT.store(args.strict, Ordering::SeqCst);
+
     match args.cmd {
+        CliCommand::Add(c) => add_profile(c).await?,
+        CliCommand::Agent(c) => run_agent(c, args.log_level.as_str()).await?,
+        CliCommand::Current(c) => current_profile(c).await?,
+        CliCommand::Lint(c) => lint_files(c).await?,
         CliCommand::List(c) => list_profiles(c).await?,
+        CliCommand::Remove(c) => remove_profile(c).await?,
+        CliCommand::Rename(c) => rename_profile(c).await?,
         CliCommand::Export(c) => export_profile(c).await?,
+        CliCommand::ServeImds(c) => serve_imds(c).await?,
+        CliCommand::ServeEcs(c) => serve_ecs(c).await?,
+        CliCommand::Shell(c) => run_shell(c).await?,
     }
 
     Ok(())
 }
 
-async fn list_profiles(args: ListCommand) -> Result<(), Box<dyn std::error::Error>> {
-    log::info!("Listing all available profiles.");
+async fn add_profile(args: AddCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, name) = match edit::resolve_target(&args.uri, !args.recipients.is_empty()).await {
+        Ok(target) => target,
+        Err(e) => {
+            log::error!("Unable to add profile '{}': {}", args.uri, e);
+            exit(1);
+        }
+    };
 
-    let credentials = AwsCredentials::load_all().await?;
+    let credentials = match read_credentials() {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            log::error!("Unable to read credentials: {}", e);
+            exit(1);
+        }
+    };
 
-    let mut lookup = AwsProfileLookup::new();
-    lookup.insert_all(credentials.sources.into_iter());
+    let mut profile = credentials.into_profile(name, file);
+    profile.region = args.region;
 
-    match &args.format {
-        ListFormat::Table => list_profiles_table(&args, &lookup),
-        ListFormat::Plain => list_profiles_plain(&args, &lookup),
-        ListFormat::Json => list_profiles_json(&args, &lookup),
-        ListFormat::Csv => list_profiles_csv(&args, &lookup),
+    let options = EditOptions {
+        force: args.force,
+        backup: args.backup,
+        recipients: args.recipients,
     };
 
-    Ok(())
+    match edit::add_profile(&profile, &options).await {
+        Ok(()) => {
+            log::info!(
+                "Added profile {} to {}",
+                profile.uri(),
+                profile.file.home_path().display()
+            );
+            Ok(())
+        }
+        Err(e) => exit_edit_error(&profile.uri(), e),
+    }
 }
 
-fn list_profiles_table(args: &ListCommand, lookup: &AwsProfileLookup) {
-    let mut table = Table::new();
-    let mut format = format::FormatBuilder::new()
-        .padding(0, 0)
-        .column_separator(' ');
+async fn remove_profile(args: RemoveCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, name) = resolve_target(&args.uri).await;
 
-    if !args.no_header {
-        format = format.separator(LinePosition::Title, LineSeparator::new('―', ' ', ' ', ' '))
+    let options = EditOptions {
+        backup: args.backup,
+        ..Default::default()
+    };
+
+    match edit::remove_profile(&file, &name, &options).await {
+        Ok(()) => {
+            log::info!(
+                "Removed profile {} from {}",
+                args.uri,
+                file.home_path().display()
+            );
+            Ok(())
+        }
+        Err(e) => exit_edit_error(&args.uri, e),
     }
+}
+
+async fn rename_profile(args: RenameCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, name) = resolve_target(&args.uri).await;
 
-    table.set_format(format.build());
+    let options = EditOptions {
+        force: args.force,
+        backup: args.backup,
+        ..Default::default()
+    };
 
-    if !args.no_header {
-        // FIXME can space cells via Cell::with_hspan(x)
-        table.set_titles(row!["profile", "prefix/profile", "priority", "file"]);
+    match edit::rename_profile(&file, &name, &args.new_name, &options).await {
+        Ok(()) => {
+            log::info!(
+                "Renamed profile {} to {} in {}",
+                args.uri,
+                args.new_name,
+                file.home_path().display()
+            );
+            Ok(())
+        }
+        Err(e) => exit_edit_error(&args.uri, e),
     }
+}
 
-    for (index, profile) in lookup.list().into_iter().enumerate() {
-        // FIXME can space cells via Cell::with_hspan(x)
+/// Find the file a profile URI refers to and the profile name, exiting if the URI is invalid.
+async fn resolve_target(uri: &str) -> (FileSource, String) {
+    match edit::resolve_target(uri, false).await {
+        Ok(target) => target,
+        Err(e) => {
+            log::error!("Invalid profile '{}': {}", uri, e);
+            exit(1);
+        }
+    }
+}
 
-        table.add_row(row![
-            Fg->profile.name,
-            Fcb->profile.uri(),
-            format!("{:02}", index),
-            FD->format!("{}", profile.file.home_path().display())
-        ]);
+fn exit_edit_error(uri: &str, e: aws_env::Error) -> ! {
+    if let Some(e) = e.downcast_ref::<ProfileNotFound>() {
+        log::error!("Unable to find profile '{}'", e.name);
+    } else if let Some(e) = e.downcast_ref::<ProfileExists>() {
+        log::error!("Profile '{}' already exists", e.uri);
+        log::error!("Use --force to replace it");
+    } else {
+        log::error!("Unable to edit profile '{}': {}", uri, e);
     }
 
-    table.printstd();
+    exit(1);
 }
 
-fn list_profiles_plain(_args: &ListCommand, lookup: &AwsProfileLookup) {
-    let mut writer = LineWriter::new(io::stdout());
+/// Prompt for credentials without echo on a terminal, otherwise read them from standard input.
+fn read_credentials() -> Result<Credentials, aws_env::Error> {
+    if !io::stdin().is_terminal() {
+        let mut input = Zeroizing::new(String::new());
+        io::stdin().read_to_string(&mut input)?;
 
-    for (priority, profile) in lookup.list().into_iter().enumerate() {
-        write!(
-            writer,
-            "{name} {uri} {priority} {file}",
-            name = profile.name,
-            uri = profile.uri(),
-            priority = priority,
-            file = profile.file.path.display()
-        )
-        .expect("unable to write to stdout");
+        return input.parse();
     }
+
+    let term = Term::stderr();
+    let prompt = |prompt: &str, allow_empty: bool| {
+        Password::new()
+            .with_prompt(prompt)
+            .allow_empty_password(allow_empty)
+            .interact_on(&term)
+            .map(Zeroizing::new)
+    };
+
+    let access_key_id = prompt("Access key id", false)?;
+    let secret_access_key = prompt("Secret access key", false)?;
+    let session_token = prompt("Session token (optional)", true)?;
+
+    Ok(Credentials {
+        access_key_id: access_key_id.trim().to_string(),
+        secret_access_key: Zeroizing::new(secret_access_key.trim().to_string()),
+        session_token: Some(session_token.trim())
+            .filter(|t| !t.is_empty())
+            .map(|t| Zeroizing::new(t.to_string())),
+        expiration: None,
+    })
 }
 
-fn list_profiles_csv(args: &ListCommand, lookup: &AwsProfileLookup) {
-    let mut writer = csv::Writer::from_writer(LineWriter::new(io::stdout()));
+/// Print the problems found in each file as `file:line:column: severity: message`, exiting with 1 if there are any
+/// errors or any file can't be read.
+async fn lint_files(args: LintCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let files = if args.files.is_empty() {
+        AwsCredentials::list_files().await?
+    } else {
+        args.files.iter().map(FileSource::from_path).collect()
+    };
+
+    let mut failed = false;
+
+    for file in files {
+        if file.encrypted && args.skip_encrypted {
+            continue;
+        }
+
+        let diagnostics = match lint::lint_file(&file).await {
+            Ok(diagnostics) => diagnostics,
+            Err(e) => {
+                log::error!("Unable to check {}: {}", file.path.display(), e);
+                failed = true;
+                continue;
+            }
+        };
 
-    if !args.no_header {
-        writer
-            .write_record(&["name", "uri", "priority", "file"])
-            .expect("unable to write header to stdout");
+        for diagnostic in diagnostics {
+            failed |= diagnostic.severity() == Severity::Error;
+            println!("{}:{}", file.home_path().display(), diagnostic);
+        }
     }
 
-    for (priority, profile) in lookup.list().iter().enumerate() {
-        writer
-            .write_record(&[
-                profile.name.as_str(),
-                profile.uri().as_str(),
-                format!("{}", priority).as_str(),
-                profile.file.path.display().to_string().as_str(),
-            ])
-            .expect("unable to write row to stdout");
+    if failed {
+        exit(1);
     }
+
+    Ok(())
 }
 
-fn list_profiles_json(_args: &ListCommand, lookup: &AwsProfileLookup) {
-    #[derive(Debug, Serialize)]
-    struct Record<'a> {
-        name: &'a str,
-        uri: String,
-        priority: usize,
-        file: String,
+async fn list_profiles(args: ListCommand) -> Result<(), Box<dyn std::error::Error>> {
+    log::info!("Listing all available profiles.");
+
+    if args.null && args.format != ListFormat::Plain {
+        log::error!("NUL-terminated records are only supported by the plain format");
+        exit(1);
     }
 
-    let values = lookup.list();
-    let mut output = Vec::with_capacity(values.len());
+    let credentials = AwsCredentials::load_all().await?;
 
-    for (priority, profile) in values.iter().enumerate() {
-        output.push(Record {
-            name: profile.name.as_str(),
-            uri: profile.uri(),
-            priority,
-            file: profile.file.path.display().to_string(),
-        });
+    let mut lookup = AwsProfileLookup::new();
+    lookup.insert_all(credentials.sources.into_iter());
+
+    let options = ListOptions {
+        columns: args.columns.clone(),
+        no_header: args.no_header,
+        null: args.null,
+    };
+
+    if args.conflicts {
+        list::print_conflicts(&lookup.conflicts(), args.format, &options)?;
+        return Ok(());
+    }
+
+    let query = match list_query(&args) {
+        Ok(query) => query,
+        Err(e) => {
+            log::error!("Invalid profile filter: {}", e);
+            exit(1);
+        }
+    };
+
+    let config = if args.columns.contains(&Column::Region) {
+        AwsConfigFile::load().await.unwrap_or_else(|e| {
+            log::warn!(
+                "Unable to load regions from {}: {}",
+                AwsConfigFile::path().display(),
+                e
+            );
+            AwsConfigFile::default()
+        })
+    } else {
+        AwsConfigFile::default()
+    };
+
+    let mut rows = list::rows(&lookup, &query, &config);
+
+    rows.retain(|row| match row.shadowed_by {
+        Some(_) => !args.effective,
+        None => !args.shadowed,
+    });
+
+    list::print_profiles(&rows, args.format, &options)?;
+
+    Ok(())
+}
+
+fn list_query(args: &ListCommand) -> Result<ProfileQuery, aws_env::Error> {
+    let mut query = ProfileQuery::new()
+        .root_only(args.root_only)
+        .has_session_token(args.has_session_token)
+        .sort(args.sort);
+
+    if let Some(prefix) = &args.prefix {
+        query = query.prefix(prefix);
+    }
+
+    if let Some(glob) = &args.glob {
+        query = query.glob(glob)?;
+    }
+
+    if let Some(regex) = &args.regex {
+        query = query.regex(regex)?;
+    }
+
+    if args.encrypted || args.plaintext {
+        query = query.encrypted(args.encrypted);
     }
 
-    serde_json::to_writer_pretty(BufWriter::new(io::stdout()), output.as_slice())
-        .expect("unable to serialize to standard output");
-    println!();
+    Ok(query)
 }
 
 fn configure_logging(level: LevelFilter) {
@@ -267,10 +633,629 @@ fn configure_logging(level: LevelFilter) {
     let _handle = log4rs::init_config(config).unwrap();
 }
 
+async fn run_agent(args: AgentCommand, log_level: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if args.kill {
+        let client = AgentClient::from_env().ok_or(format!(
+            "${} is not set, unable to find an agent to stop",
+            agent::SOCKET_ENV_VAR
+        ))?;
+
+        client.stop().await.map_err(|e| e.to_string())?;
+
+        println!("unset {};", agent::SOCKET_ENV_VAR);
+        println!("unset {};", agent::PID_ENV_VAR);
+
+        return Ok(());
+    }
+
+    let socket = args.socket.unwrap_or_else(agent::default_socket_path);
+
+    if args.foreground {
+        agent::harden_process();
+        print_agent_env(&socket, std::process::id());
+
+        return Agent::new(&socket, Duration::from_secs(args.ttl))
+            .serve()
+            .await
+            .map_err(|e| e.to_string().into());
+    }
+
+    // re-run ourselves in the foreground in a new session, detached from the terminal
+    let mut command = Command::new(std::env::current_exe()?);
+
+    command
+        .arg("--log-level")
+        .arg(log_level)
+        .arg("agent")
+        .arg("--foreground")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--ttl")
+        .arg(args.ttl.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // SAFETY: setsid is async-signal-safe and is the only thing done between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        });
+    }
+
+    let mut child = command.spawn()?;
+
+    // wait for the agent to start accepting connections
+    for _ in 0..50 {
+        if let Some(status) = child.try_wait()? {
+            return Err(format!("agent exited during startup ({})", status).into());
+        }
+
+        if tokio::net::UnixStream::connect(&socket).await.is_ok() {
+            print_agent_env(&socket, child.id());
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    Err(format!("agent did not start listening on {}", socket.display()).into())
+}
+
+fn print_agent_env(socket: &Path, pid: u32) {
+    // NOTE mirrors ssh-agent's output so that `eval $(aws-env agent)` works in the same way
+    println!(
+        "{var}={socket}; export {var};",
+        var = agent::SOCKET_ENV_VAR,
+        socket = socket.display()
+    );
+    println!(
+        "{var}={pid}; export {var};",
+        var = agent::PID_ENV_VAR,
+        pid = pid
+    );
+}
+
 async fn export_profile(args: ExportCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let options = ExportOptions {
+        secret_name: args.secret_name,
+        namespace: args.namespace,
+        metadata: args.metadata,
+    };
+
+    // GitLab can only mask variables defined in its settings, so never print these into the job log
+    let gitlab_ci = args.template.is_none() && args.format == ExportFormat::GitlabCi;
+
+    let mut template = match args.template {
+        Some(name) => Some(load_template(name.as_str()).await),
+        None => None,
+    };
+
+    // NOTE every profile is resolved before anything is written, so a missing profile never leaves a partial export
+    let mut profiles = Vec::with_capacity(args.multi.len().max(1));
+
+    if args.multi.is_empty() {
+        let profile = match args.name {
+            Some(name) => resolve_profile(name.as_str()).await,
+            None => pick_profile().await,
+        };
+
+        profiles.push((String::new(), profile));
+    } else {
+        if template.is_none() {
+            match args.format.dialect() {
+                Some(dialect) => template = Some(Template::from(dialect)),
+                None => {
+                    log::error!("The {} format can't export multiple profiles", args.format);
+                    exit(1);
+                }
+            }
+        }
+
+        for pair in args.multi.iter() {
+            match pair.split_once('=') {
+                Some((alias, name)) if !alias.is_empty() => {
+                    profiles.push((alias.to_string(), resolve_profile(name).await))
+                }
+                _ => {
+                    log::error!("Expected ALIAS=PROFILE, got '{}'", pair);
+                    exit(1);
+                }
+            }
+        }
+    }
+
+    if let Some(template) = template.as_mut().filter(|_| args.metadata) {
+        template.push_metadata();
+    }
+
+    let write = |mut writer: &mut dyn Write| match (&template, profiles.as_slice()) {
+        (Some(template), [(alias, profile)]) if alias.is_empty() => {
+            export::write_template(&mut writer, profile, template)
+        }
+        (Some(template), profiles) => export::write_multi(
+            &mut writer,
+            profiles
+                .iter()
+                .map(|(alias, profile)| (alias.as_str(), profile))
+                .collect::<Vec<_>>()
+                .as_slice(),
+            template,
+        ),
+        (None, profiles) => {
+            export::write_profile(&mut writer, &profiles[0].1, args.format, &options)
+        }
+    };
+
+    let result = if let Some(fd) = args.fd {
+        // SAFETY: F_GETFD only checks whether the descriptor is open
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+            log::error!("File descriptor {} is not open", fd);
+            exit(1);
+        }
+
+        // SAFETY: the descriptor is open and is ours to use, as it was handed to us on the command-line
+        let mut file = unsafe { File::from_raw_fd(fd) };
+
+        write(&mut file)
+    } else if let Some(path) = args.to_file {
+        let mut file = match export::create_private_file(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Unable to create {}: {}", path.display(), e);
+                exit(1);
+            }
+        };
+
+        write(&mut file).and_then(|_| file.commit())
+    } else if gitlab_ci {
+        log::error!("GitLab CI can't mask exported credentials in job logs, use --to-file to write a dotenv report");
+        log::error!("Credentials which must stay masked belong in the project's masked CI/CD variables instead");
+        exit(1);
+    } else {
+        output_profile(write)
+    };
+
+    if let Err(e) = result {
+        let uris: Vec<String> = profiles.iter().map(|(_, p)| p.uri()).collect();
+        log::error!("Unable to export profile {}: {}", uris.join(", "), e);
+        exit(1);
+    }
+
+    Ok(())
+}
+
+async fn serve_imds(args: ServeImdsCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let profile = resolve_profile(args.name.as_str()).await;
+    let listener = std::net::TcpListener::bind(args.listen)?;
+
+    log::info!(
+        "Serving profile {} via IMDSv2 on http://{}/ as role {}",
+        profile.uri(),
+        listener.local_addr()?,
+        args.role
+    );
+
+    println!(
+        "{var}=http://{address}/; export {var};",
+        var = "AWS_EC2_METADATA_SERVICE_ENDPOINT",
+        address = listener.local_addr()?
+    );
+
+    Imds::new(profile, args.role, Duration::from_secs(args.lifetime))
+        .serve(listener, shutdown_signal())
+        .await
+        .map_err(|e| e.to_string().into())
+}
+
+async fn serve_ecs(args: ServeEcsCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let profile = resolve_profile(args.name.as_str()).await;
+    let listener = std::net::TcpListener::bind(args.listen)?;
+    let address = listener.local_addr()?;
+
+    log::info!(
+        "Serving profile {} via the ECS credentials endpoint on http://{}/",
+        profile.uri(),
+        address
+    );
+
+    let ecs = Ecs::new(profile, Duration::from_secs(args.lifetime)).map_err(|e| e.to_string())?;
+
+    let host = args.host.unwrap_or_else(|| address.ip().to_string());
+
+    // NOTE print in the form of an env file, so the output can be redirected into a docker-compose `env_file`
+    println!(
+        "{var}=http://{host}:{port}{path}",
+        var = ecs::FULL_URI_ENV_VAR,
+        // NOTE IPv6 addresses need brackets within a URI
+        host = match host.parse::<Ipv6Addr>() {
+            Ok(_) => format!("[{}]", host),
+            Err(_) => host,
+        },
+        port = address.port(),
+        path = ecs::CREDENTIALS_PATH
+    );
+    println!(
+        "{var}={token}",
+        var = ecs::AUTHORIZATION_TOKEN_ENV_VAR,
+        token = ecs.token()
+    );
+    io::stdout().flush()?;
+
+    ecs.serve(listener, shutdown_signal())
+        .await
+        .map_err(|e| e.to_string().into())
+}
+
+/// Start `$SHELL` with the profile's environment, warning before its credentials expire.
+async fn run_shell(args: ShellCommand) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(uri) = std::env::var_os(shell::SHELL_VAR).filter(|_| !args.force) {
+        log::error!(
+            "Already inside an aws-env shell for {}, exit it first or use --force",
+            uri.to_string_lossy()
+        );
+        exit(1);
+    }
+
+    let profile = match args.name {
+        Some(name) => resolve_profile(name.as_str()).await,
+        None => pick_profile().await,
+    };
+
+    let program = std::env::var_os("SHELL")
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/bin/sh"));
+
+    let mut child = match shell::command(&profile, &program, args.prompt).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            log::error!("Unable to start {}: {}", program.display(), e);
+            exit(1);
+        }
+    };
+
+    // NOTE the terminal may deliver ctrl-c and ctrl-\ to us as well as to the shell, which handles them itself
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut quit = signal(SignalKind::quit())?;
+
+    let before = Duration::from_secs(args.warn_before);
+    let mut last = SystemTime::now();
+
+    if let Some(expiration) = profile.expiration {
+        if expiration.checked_sub(before).is_none_or(|w| w <= last) {
+            warn_expiration(&profile, expiration);
+        }
+    }
+
+    let status = loop {
+        let warning = profile
+            .expiration
+            .and_then(|e| shell::next_warning(e, before, last));
+
+        let expiring = async {
+            match warning {
+                Some(w) => {
+                    tokio::time::sleep(w.duration_since(SystemTime::now()).unwrap_or_default())
+                        .await
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            status = child.wait() => break status?,
+            _ = interrupt.recv() => (),
+            _ = quit.recv() => (),
+            _ = expiring => {
+                last = warning.unwrap();
+                warn_expiration(&profile, profile.expiration.unwrap());
+            }
+        }
+    };
+
+    exit(
+        status
+            .code()
+            .unwrap_or_else(|| 128 + status.signal().unwrap_or(0)),
+    );
+}
+
+fn warn_expiration(profile: &AwsProfile, expiration: SystemTime) {
+    // NOTE printed directly rather than logged, as the shell's user needs to see it regardless of the log level
+    match expiration.duration_since(SystemTime::now()) {
+        Ok(remaining) => eprintln!(
+            "aws-env: the credentials for {} expire in {}",
+            profile.uri(),
+            humantime::format_duration(Duration::from_secs(remaining.as_secs()))
+        ),
+        Err(_) => eprintln!(
+            "aws-env: the credentials for {} have expired",
+            profile.uri()
+        ),
+    }
+}
+
+/// Completes when the process receives `SIGINT` or `SIGTERM`.
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("unable to listen for SIGTERM");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        _ = terminate.recv() => (),
+    }
+}
+
+/// The configuration file, read at most once and only by the commands which need it.
+async fn config() -> &'static Result<aws_env::config::Config, String> {
+    CONFIG
+        .get_or_init(|| async {
+            aws_env::config::Config::load()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await
+}
+
+/// Load the configuration file, exiting if it can't be read.
+async fn load_config() -> &'static aws_env::config::Config {
+    match config().await {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("Unable to load configuration: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Load an export template from the configuration file, exiting if there is no such template.
+async fn load_template(name: &str) -> Template {
+    match load_config().await.templates.get(name) {
+        Some(template) => template.clone(),
+        None => {
+            log::error!(
+                "Unable to find template '{}' in {}",
+                name,
+                aws_env::config::Config::path().display()
+            );
+            exit(1);
+        }
+    }
+}
+
+async fn current_profile(args: CurrentCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let access_key_id = match std::env::var(export::ACCESS_KEY_ID_VAR) {
+        Ok(access_key_id) if !access_key_id.is_empty() => access_key_id,
+        _ => {
+            log::error!(
+                "${} is not set, no profile is exported",
+                export::ACCESS_KEY_ID_VAR
+            );
+            exit(1);
+        }
+    };
+
+    // NOTE the profile named in the metadata can be resolved without decrypting everything, so try it first
+    let exported = match std::env::var(export::PROFILE_VAR) {
+        Ok(name) => find_profile(name.as_str())
+            .await
+            .ok()
+            .filter(|p| p.access_key_id.eq(&access_key_id)),
+        Err(_) => None,
+    };
+
+    let profiles = match exported {
+        Some(profile) => vec![profile],
+        None => {
+            let mut lookup = AwsProfileLookup::new();
+            lookup.insert_all(AwsCredentials::load_all().await?.sources.into_iter());
+
+            lookup
+                .by_access_key_id(access_key_id.as_str())
+                .into_iter()
+                .cloned()
+                .collect()
+        }
+    };
+
+    if profiles.is_empty() {
+        log::error!(
+            "The access key id in ${} doesn't belong to any known profile",
+            export::ACCESS_KEY_ID_VAR
+        );
+        exit(1);
+    }
+
+    for profile in profiles {
+        if args.long {
+            println!(
+                "{}\t{}\t{}",
+                profile.uri(),
+                profile.file.home_path().display(),
+                profile
+                    .expiration
+                    .map(|e| humantime::format_rfc3339_seconds(e).to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            );
+        } else {
+            println!("{}", profile.uri());
+        }
+    }
+
+    Ok(())
+}
+
+/// A choice in the profile picker: either a profile, or an encrypted file whose profiles can't be listed without
+/// decrypting it.
+enum Candidate {
+    Profile(AwsProfile),
+    Encrypted(FileSource),
+}
+
+impl Display for Candidate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Candidate::Profile(p) => write!(f, "{:<32} {}", p.uri(), p.file.home_path().display()),
+            Candidate::Encrypted(source) => write!(
+                f,
+                "{:<32} {} (encrypted)",
+                format!("{}/*", source.prefix().unwrap_or_default()),
+                source.home_path().display()
+            ),
+        }
+    }
+}
+
+/// Let the user pick a profile with a fuzzy-search over every profile.
+///
+/// Encrypted files are only decrypted once picked, after which their profiles are offered in turn. The picker is drawn
+/// on standard error, so that standard output can still be captured, e.g. by `eval "$(aws-env export)"`.
+async fn pick_profile() -> AwsProfile {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        log::error!("No profile name given, and no terminal to pick one on");
+        exit(1);
+    }
+
+    let sources = match AwsCredentials::list_files().await {
+        Ok(sources) => sources,
+        Err(e) => {
+            log::error!("Unable to find credential files: {}", e);
+            exit(1);
+        }
+    };
+
+    let mut candidates = Vec::new();
+
+    for source in sources {
+        if source.encrypted {
+            candidates.push(Candidate::Encrypted(source));
+            continue;
+        }
+
+        match AwsCredentialsFile::load(&source.path).await {
+            Ok(file) => candidates.extend(
+                file.profiles
+                    .into_iter()
+                    .map(|(_, p)| Candidate::Profile(p)),
+            ),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+
+    candidates.sort_by_cached_key(|c| c.to_string());
+
+    loop {
+        let source = match pick("Profile", candidates) {
+            Candidate::Profile(profile) => return profile,
+            Candidate::Encrypted(source) => source,
+        };
+
+        candidates = match AwsCredentialsFile::load_encrypted(&source.path).await {
+            Ok(file) => file
+                .profiles
+                .into_iter()
+                .map(|(_, p)| Candidate::Profile(p))
+                .collect(),
+            Err(e) => {
+                log::error!("{}", e);
+                exit(1);
+            }
+        };
+    }
+}
+
+/// Show a fuzzy-search picker over the candidates, exiting if nothing is picked.
+///
+/// A single candidate is picked without asking.
+fn pick(prompt: &str, mut candidates: Vec<Candidate>) -> Candidate {
+    match candidates.len() {
+        0 => {
+            log::error!("No profiles found");
+            exit(1);
+        }
+        1 => return candidates.remove(0),
+        _ => (),
+    }
+
+    let picked = FuzzySelect::new()
+        .with_prompt(prompt)
+        .items(&candidates)
+        .default(0)
+        .interact_on_opt(&Term::stderr());
+
+    match picked {
+        Ok(Some(index)) => candidates.swap_remove(index),
+        Ok(None) => exit(1),
+        Err(e) => {
+            log::error!("Unable to pick a profile: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Resolve a profile by URI or bare name, exiting if it can't be found.
+///
+/// A running agent is used if `$AWS_ENV_AGENT_SOCK` is set, otherwise URIs in the root namespace are first looked up
+/// in `~/.aws/credentials` alone before falling back to loading all credential files.
+async fn resolve_profile(name: &str) -> AwsProfile {
+    let e = match find_profile(name).await {
+        Ok(profile) => return profile,
+        Err(e) => e,
+    };
+
+    if let Some(e) = e.downcast_ref::<ProfileNotFound>() {
+        log::error!("Unable to find profile '{}'", name);
+
+        if !e.suggestions.is_empty() {
+            let suggestions: Vec<String> = e.suggestions.iter().map(|s| s.to_string()).collect();
+            log::error!("Did you mean {}?", suggestions.join(", "));
+        }
+    } else if let Some(e) = e.downcast_ref::<AmbiguousProfile>() {
+        log::error!("Profile '{}' is defined in more than one file", name);
+        log::error!("Use one of {}", e.uris.join(", "));
+    } else {
+        log::error!("Unable to find profile '{}': {}", name, e);
+    }
+
+    exit(1);
+}
+
+/// Find a profile, failing with either a `ProfileNotFound` or, in strict mode, an `AmbiguousProfile`.
+async fn find_profile(name: &str) -> Result<AwsProfile, aws_env::Error> {
+    // NOTE a configuration which can't be loaded only stops the commands using templates
+    let strict = STRICT.load(Ordering::SeqCst)
+        || match config().await {
+            Ok(config) => config.strict,
+            Err(e) => {
+                log::warn!("Ignoring the configuration's strict setting: {}", e);
+                false
+            }
+        };
+
+    if let Some(client) = AgentClient::from_env() {
+        let client = client.strict(strict);
+
+        match client.get(name).await {
+            Ok(p) => {
+                log::debug!("Agent returned profile {}", p.uri());
+                return Ok(p);
+            }
+            Err(e) if e.is::<ProfileNotFound>() || e.is::<AmbiguousProfile>() => return Err(e),
+            Err(e) => log::warn!(
+                "Unable to use agent at {}, loading credentials directly: {}",
+                client.socket().display(),
+                e
+            ),
+        }
+    }
+
     let mut lookup = AwsProfileLookup::new();
 
-    if args.name.as_str().starts_with("/") {
+    if name.starts_with('/') {
         // if the name starts with a slash, assume it's in ~/.aws/credentials
         log::debug!("Attempting to load profile from ~/.aws/credentials...");
 
@@ -284,20 +1269,19 @@ async fn export_profile(args: ExportCommand) -> Result<(), Box<dyn std::error::E
         {
             lookup.insert(f);
         }
-    } else if args.name.as_str().contains("/") {
+    } else if name.contains('/') {
         // if the name contains a slash, attempt to load the specific file-stem(s)
         // FIXME implement file-stem insertion
     }
 
     // try to look up
-    if let Some(p) = lookup.by_uri(args.name.as_str()) {
+    if let Some(p) = lookup.by_uri(name) {
         log::debug!(
             "Lazy loading successful, found profile {} in {}",
             p.name.as_str(),
             p.file.home_path().display()
         );
-        output_profile(p).expect("unable to write profile to stdout");
-        return Ok(());
+        return Ok(p.clone());
     }
 
     // if we've made it this far, lazy-loading has failed so load everything
@@ -311,49 +1295,24 @@ async fn export_profile(args: ExportCommand) -> Result<(), Box<dyn std::error::E
         }
     };
 
-    if let Some(p) = lookup
-        .by_uri(args.name.as_str())
-        .or_else(|| lookup.by_name(args.name.as_str()))
-    {
+    let profile = if strict {
+        lookup.find_strict(name).cloned()
+    } else {
+        lookup.find(name).cloned().map_err(aws_env::Error::from)
+    };
+
+    if let Ok(p) = &profile {
         log::debug!(
             "Located profile {} in {}",
-            args.name.as_str(),
+            name,
             p.file.home_path().display()
         );
-        output_profile(p).unwrap();
-        Ok(())
-    } else {
-        log::error!("Unable to find profile '{}'", args.name.as_str());
-        exit(1);
     }
+
+    profile
 }
 
-fn output_profile(profile: &AwsProfile) -> io::Result<()> {
+fn output_profile<F: FnOnce(&mut dyn Write) -> io::Result<()>>(write: F) -> io::Result<()> {
     // NOTE we can't output buffer without placing credentials into memory we can't manage, so don't buffer
-    let mut writer = io::stdout();
-
-    // access key id
-    write!(
-        writer,
-        "export AWS_ACCESS_KEY_ID={}\n",
-        profile.access_key_id
-    )?;
-
-    // secret access key
-    write!(
-        writer,
-        "export AWS_SECRET_ACCESS_KEY={}\n",
-        profile.secret_access_key.as_str()
-    )?;
-
-    if let Some(session_token) = &profile.session_token {
-        // optional session token
-        write!(
-            writer,
-            "export AWS_SESSION_TOKEN={}\n",
-            session_token.as_str()
-        )?;
-    }
-
-    Ok(())
+    write(&mut io::stdout())
 }
@@ -0,0 +1,203 @@
+#[cfg(test)]
+mod tests;
+
+use crate::{AwsProfile, AwsProfileLookup, Error};
+
+use regex::Regex;
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The keys profiles can be sorted by, as accepted by `SortKey::from_str`.
+pub const SORT_KEYS: &[&str] = &["name", "uri", "file", "priority"];
+
+/// What to sort the results of a query by.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SortKey {
+    Name,
+    Uri,
+    File,
+    /// Load order, lowest priority first.
+    #[default]
+    Priority,
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            SortKey::Name => "name",
+            SortKey::Uri => "uri",
+            SortKey::File => "file",
+            SortKey::Priority => "priority",
+        })
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "name" => Ok(SortKey::Name),
+            "uri" => Ok(SortKey::Uri),
+            "file" => Ok(SortKey::File),
+            "priority" => Ok(SortKey::Priority),
+            _ => Err(format!("unknown sort key {}", s).into()),
+        }
+    }
+}
+
+/// A filter over the profiles of an `AwsProfileLookup`, run with `AwsProfileLookup::query`.
+///
+/// Every condition set must hold for a profile to match, so an empty query matches every profile.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileQuery {
+    prefix: Option<String>,
+    pattern: Option<Regex>,
+    encrypted: Option<bool>,
+    root_only: bool,
+    has_session_token: bool,
+    sort: SortKey,
+}
+
+impl ProfileQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match profiles in files with the given prefix, i.e. `work` for `~/.aws/credentials.d/work.ini`.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Only match profiles whose name or URI matches the given glob in its entirety.
+    ///
+    /// Globs support `*` for any run of characters, `?` for any single character and character classes like `[a-z]`
+    /// or `[!0-9]`.
+    pub fn glob(mut self, glob: &str) -> Result<Self, Error> {
+        self.pattern = Some(Regex::new(&glob_to_regex(glob))?);
+        Ok(self)
+    }
+
+    /// Only match profiles whose name or URI contains a match for the given regular expression.
+    pub fn regex(mut self, regex: &str) -> Result<Self, Error> {
+        self.pattern = Some(Regex::new(regex)?);
+        Ok(self)
+    }
+
+    /// Only match profiles from encrypted files if `true`, or from plaintext files if `false`.
+    pub fn encrypted(mut self, encrypted: bool) -> Self {
+        self.encrypted = Some(encrypted);
+        self
+    }
+
+    /// Only match profiles in `~/.aws/credentials`.
+    pub fn root_only(mut self, root_only: bool) -> Self {
+        self.root_only = root_only;
+        self
+    }
+
+    /// Only match profiles with a session token, i.e. temporary credentials.
+    pub fn has_session_token(mut self, has_session_token: bool) -> Self {
+        self.has_session_token = has_session_token;
+        self
+    }
+
+    /// Sort the results by the given key. Ties are kept in priority order.
+    pub fn sort(mut self, sort: SortKey) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Whether the given profile matches every condition of the query.
+    pub fn matches(&self, profile: &AwsProfile) -> bool {
+        if let Some(prefix) = &self.prefix {
+            if profile.prefix().as_ref() != Some(prefix) {
+                return false;
+            }
+        }
+
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(&profile.name) && !pattern.is_match(&profile.uri()) {
+                return false;
+            }
+        }
+
+        if let Some(encrypted) = self.encrypted {
+            if profile.file.encrypted != encrypted {
+                return false;
+            }
+        }
+
+        (!self.root_only || profile.is_in_root())
+            && (!self.has_session_token || profile.session_token.is_some())
+    }
+}
+
+impl<'a> AwsProfileLookup {
+    /// Run the given query, returning matching profiles along with their priority, as in the index into `list`.
+    pub fn query(&'a self, query: &ProfileQuery) -> Vec<(usize, &'a AwsProfile)> {
+        let mut result: Vec<(usize, &AwsProfile)> = self
+            .list()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, profile)| query.matches(profile))
+            .collect();
+
+        match query.sort {
+            SortKey::Name => result.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name)),
+            SortKey::Uri => result.sort_by_cached_key(|(_, p)| p.uri()),
+            SortKey::File => result.sort_by(|(_, a), (_, b)| a.file.path.cmp(&b.file.path)),
+            SortKey::Priority => {}
+        }
+
+        result
+    }
+}
+
+/// Translate a glob into an anchored regular expression.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^(?:");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' if glob_class_end(chars.clone()) => {
+                regex.push('[');
+
+                if chars.next_if(|c| *c == '!' || *c == '^').is_some() {
+                    regex.push('^');
+                }
+
+                // NOTE a leading ']' is part of the class rather than closing it
+                if chars.next_if_eq(&']').is_some() {
+                    regex.push_str("\\]");
+                }
+
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '-' => regex.push('-'),
+                        c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                    }
+                }
+
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    regex.push_str(")$");
+    regex
+}
+
+/// Whether a character class starting just after a `[` is closed, otherwise the `[` is literal.
+fn glob_class_end<I: Iterator<Item = char>>(mut chars: std::iter::Peekable<I>) -> bool {
+    chars.next_if(|c| *c == '!' || *c == '^');
+    chars.next_if_eq(&']');
+    chars.any(|c| c == ']')
+}
@@ -0,0 +1,118 @@
+use super::{glob_to_regex, ProfileQuery, SortKey};
+use crate::loader::FileSource;
+use crate::{AwsCredentialsFile, AwsProfile, AwsProfileLookup};
+
+use indexmap::IndexMap;
+
+use regex::Regex;
+
+fn lookup() -> AwsProfileLookup {
+    let aws_dir = crate::utils::homedir().join(".aws");
+    let creds_d = aws_dir.join("credentials.d");
+    let mut lookup = AwsProfileLookup::new();
+
+    for (path, profiles) in [
+        (aws_dir.join("credentials"), vec![("default", false)]),
+        (
+            creds_d.join("work.asc"),
+            vec![("prod", true), ("staging", false)],
+        ),
+        (creds_d.join("client.ini"), vec![("prod", false)]),
+    ] {
+        let mut credentials_file = AwsCredentialsFile {
+            file: FileSource::from_path(path),
+            profiles: IndexMap::new(),
+        };
+
+        for (name, session_token) in profiles {
+            credentials_file.push(AwsProfile::new(
+                name,
+                "AKIAEXAMPLE",
+                String::new(),
+                session_token.then(|| "token".to_string()),
+                credentials_file.file.clone(),
+            ));
+        }
+
+        lookup.insert(credentials_file);
+    }
+
+    lookup
+}
+
+fn uris(lookup: &AwsProfileLookup, query: ProfileQuery) -> Vec<String> {
+    lookup
+        .query(&query)
+        .into_iter()
+        .map(|(_, p)| p.uri())
+        .collect()
+}
+
+#[test]
+fn test_query() {
+    let lookup = lookup();
+
+    assert_eq!(
+        vec!["client/prod", "work/prod", "work/staging", "/default"],
+        uris(&lookup, ProfileQuery::new())
+    );
+    assert_eq!(
+        vec!["work/prod", "work/staging"],
+        uris(&lookup, ProfileQuery::new().prefix("work"))
+    );
+    assert_eq!(
+        vec!["client/prod", "work/prod"],
+        uris(&lookup, ProfileQuery::new().glob("pr?d").unwrap())
+    );
+    assert_eq!(
+        vec!["work/prod", "work/staging"],
+        uris(&lookup, ProfileQuery::new().glob("work/*").unwrap())
+    );
+    assert_eq!(
+        vec!["work/staging"],
+        uris(&lookup, ProfileQuery::new().regex("^s|ing$").unwrap())
+    );
+    assert_eq!(
+        vec!["work/prod", "work/staging"],
+        uris(&lookup, ProfileQuery::new().encrypted(true))
+    );
+    assert_eq!(
+        vec!["/default"],
+        uris(&lookup, ProfileQuery::new().root_only(true))
+    );
+    assert_eq!(
+        vec!["work/prod"],
+        uris(&lookup, ProfileQuery::new().has_session_token(true))
+    );
+    assert!(ProfileQuery::new().regex("(").is_err());
+
+    // sorting keeps ties in priority order, and priorities are kept
+    let sorted: Vec<(usize, String)> = lookup
+        .query(&ProfileQuery::new().encrypted(false).sort(SortKey::Name))
+        .into_iter()
+        .map(|(priority, p)| (priority, p.uri()))
+        .collect();
+
+    assert_eq!(
+        vec![(3, "/default".to_string()), (0, "client/prod".to_string())],
+        sorted
+    );
+    assert_eq!(
+        vec!["/default", "client/prod", "work/prod", "work/staging"],
+        uris(&lookup, ProfileQuery::new().sort("uri".parse().unwrap()))
+    );
+}
+
+#[test]
+fn test_globs() {
+    let matches = |glob: &str, text: &str| Regex::new(&glob_to_regex(glob)).unwrap().is_match(text);
+
+    assert!(matches("prod-*", "prod-eu"));
+    assert!(!matches("prod-*", "my-prod-eu"));
+    assert!(matches("prod.?", "prod.1"));
+    assert!(!matches("prod.?", "prodx1"));
+    assert!(matches("env-[0-9]", "env-3"));
+    assert!(!matches("env-[!0-9]", "env-3"));
+    assert!(matches("[]x]", "]"));
+    assert!(matches("a[b", "a[b"));
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests;
+
+use crate::export::{Dialect, Template};
+use crate::AwsProfile;
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use tokio::process::Command;
+
+/// The marker variable set inside `aws-env shell` to the URI of the profile the shell was started with.
+pub const SHELL_VAR: &str = "AWS_ENV_SHELL";
+
+/// The prompt variable prefixed with the profile's URI when asked to.
+const PROMPT_VAR: &str = "PS1";
+
+/// Build the command starting the given shell with the profile's credentials and metadata in its environment.
+///
+/// Credential variables the profile doesn't have are removed rather than inherited, so that a session token or
+/// expiration left over from another profile never gets mixed with this one's keys. If `prompt` is set, `$PS1` is
+/// prefixed with the profile's URI in parentheses, which only shells that don't set their prompt in their startup
+/// files will show.
+pub fn command<P: AsRef<Path>>(profile: &AwsProfile, shell: P, prompt: bool) -> Command {
+    let mut template = Template::from(Dialect::Shell);
+    template.push_metadata();
+
+    let mut command = Command::new(shell.as_ref());
+
+    for (variable, field) in template.variables.iter() {
+        match field.value(profile) {
+            Some(value) => command.env(variable, value.as_ref()),
+            None => command.env_remove(variable),
+        };
+    }
+
+    command.env(SHELL_VAR, profile.uri());
+
+    if prompt {
+        let current = std::env::var(PROMPT_VAR).unwrap_or_else(|_| "$ ".to_string());
+        command.env(PROMPT_VAR, format!("({}) {}", profile.uri(), current));
+    }
+
+    command
+}
+
+/// When to next warn that credentials expiring at `expiration` are about to, given that the last warning was at
+/// `last`.
+///
+/// Warnings are due `before` the credentials expire and again once they have, and `None` once both have passed.
+pub fn next_warning(
+    expiration: SystemTime,
+    before: Duration,
+    last: SystemTime,
+) -> Option<SystemTime> {
+    let warnings = [
+        expiration.checked_sub(before).unwrap_or(expiration),
+        expiration,
+    ];
+
+    warnings.into_iter().find(|w| *w > last)
+}
@@ -0,0 +1,59 @@
+use super::{command, next_warning, SHELL_VAR};
+use crate::loader::FileSource;
+use crate::AwsProfile;
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_command_environment() {
+    let mut profile = AwsProfile::new(
+        "prod",
+        "AKIAEXAMPLE",
+        "secret".into(),
+        None,
+        FileSource::from_path(
+            crate::utils::homedir()
+                .join(".aws")
+                .join("credentials.d")
+                .join("work.asc"),
+        ),
+    );
+
+    profile.region = Some("eu-west-1".into());
+
+    let command = command(&profile, "/bin/sh", true);
+    let env: HashMap<&OsStr, Option<&OsStr>> = command.as_std().get_envs().collect();
+
+    let value = |name: &str| env.get(OsStr::new(name)).copied().flatten();
+
+    assert_eq!(Some(OsStr::new("AKIAEXAMPLE")), value("AWS_ACCESS_KEY_ID"));
+    assert_eq!(Some(OsStr::new("secret")), value("AWS_SECRET_ACCESS_KEY"));
+    assert_eq!(Some(OsStr::new("work/prod")), value("AWS_ENV_PROFILE"));
+    assert_eq!(
+        Some(OsStr::new("~/.aws/credentials.d/work.asc")),
+        value("AWS_ENV_SOURCE")
+    );
+    assert_eq!(Some(OsStr::new("work/prod")), value(SHELL_VAR));
+    assert!(value("PS1")
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("(work/prod) "));
+
+    // variables the profile doesn't have are removed rather than inherited
+    for name in ["AWS_SESSION_TOKEN", "AWS_CREDENTIAL_EXPIRATION"] {
+        assert_eq!(Some(&None), env.get(OsStr::new(name)));
+    }
+}
+
+#[test]
+fn test_next_warning() {
+    let expiration = UNIX_EPOCH + Duration::from_secs(3600);
+    let before = Duration::from_secs(300);
+    let warning = UNIX_EPOCH + Duration::from_secs(3300);
+
+    assert_eq!(Some(warning), next_warning(expiration, before, UNIX_EPOCH));
+    assert_eq!(Some(expiration), next_warning(expiration, before, warning));
+    assert_eq!(None, next_warning(expiration, before, expiration));
+}
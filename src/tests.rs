@@ -1,4 +1,4 @@
-use super::{AwsCredentialsFile, AwsProfile, AwsProfileLookup};
+use super::{AmbiguousProfile, AwsCredentialsFile, AwsProfile, AwsProfileLookup};
 use crate::loader::FileSource;
 use indexmap::IndexMap;
 use zeroize::Zeroizing;
@@ -21,6 +21,8 @@ fn test_name_lookup() {
             access_key_id: access_key_id.into(),
             secret_access_key: Zeroizing::new(String::new()),
             session_token: None,
+            region: None,
+            expiration: None,
         });
     }
 
@@ -69,6 +71,8 @@ fn test_name_lookup() {
         access_key_id: "4".into(),
         secret_access_key: Zeroizing::new(String::new()),
         session_token: None,
+        region: None,
+        expiration: None,
         file: f99.file.clone(),
     });
 
@@ -95,6 +99,8 @@ fn test_name_lookup() {
         access_key_id: "5".into(),
         secret_access_key: Zeroizing::new(String::new()),
         session_token: None,
+        region: None,
+        expiration: None,
         file: f98.file.clone(),
     });
 
@@ -122,6 +128,8 @@ fn test_name_lookup() {
         access_key_id: "100".into(),
         secret_access_key: Zeroizing::new(String::new()),
         session_token: None,
+        region: None,
+        expiration: None,
         file: f99_enc.file.clone(),
     });
 
@@ -144,3 +152,161 @@ fn test_name_lookup() {
 
 #[test]
 fn test_prefix_lookup() {}
+
+#[test]
+fn test_access_key_id_lookup() {
+    let creds_d = crate::utils::homedir().join(".aws").join("credentials.d");
+    let mut lookup = AwsProfileLookup::new();
+
+    for (file, profiles) in [
+        ("01-first.ini", vec![("a", "1")]),
+        ("02-second.ini", vec![("b", "1"), ("c", "2")]),
+    ] {
+        let mut credentials_file = AwsCredentialsFile {
+            file: FileSource::from_path(creds_d.join(file)),
+            profiles: IndexMap::new(),
+        };
+
+        for (profile_name, access_key_id) in profiles {
+            credentials_file.push(AwsProfile::new(
+                profile_name,
+                access_key_id,
+                String::new(),
+                None,
+                credentials_file.file.clone(),
+            ));
+        }
+
+        lookup.insert(credentials_file);
+    }
+
+    let uris: Vec<String> = lookup
+        .by_access_key_id("1")
+        .iter()
+        .map(|p| p.uri())
+        .collect();
+
+    // higher priority files come first
+    assert_eq!(vec!["02-second/b", "01-first/a"], uris);
+    assert!(lookup.by_access_key_id("3").is_empty());
+}
+
+#[test]
+fn test_suggestions() {
+    let creds_d = crate::utils::homedir().join(".aws").join("credentials.d");
+    let mut lookup = AwsProfileLookup::new();
+
+    for (file, names) in [
+        ("client.ini", vec!["prod", "staging"]),
+        ("work.ini", vec!["prod", "production-eu", "dev"]),
+    ] {
+        let mut credentials_file = AwsCredentialsFile {
+            file: FileSource::from_path(creds_d.join(file)),
+            profiles: IndexMap::new(),
+        };
+
+        for name in names {
+            credentials_file.push(AwsProfile::new(
+                name,
+                "AKIAEXAMPLE",
+                String::new(),
+                None,
+                credentials_file.file.clone(),
+            ));
+        }
+
+        lookup.insert(credentials_file);
+    }
+
+    let suggest = |name: &str| -> Vec<String> {
+        lookup.suggest(name).iter().map(|s| s.to_string()).collect()
+    };
+
+    // prefixes come first, and ambiguous names list every URI in priority order
+    assert_eq!(
+        vec!["prod (one of work/prod, client/prod)", "work/production-eu"],
+        suggest("pro")
+    );
+
+    // then substrings, then typos
+    assert_eq!(vec!["work/production-eu"], suggest("ction"));
+    assert_eq!(vec!["client/staging"], suggest("stagign"));
+    assert_eq!(vec!["work/dev"], suggest("DEV"));
+    assert_eq!(vec!["work/dev"], suggest("work/deb"));
+    assert!(suggest("nothing-like-it").is_empty());
+
+    let error = lookup.find("stagign").unwrap_err();
+
+    assert_eq!(
+        "unable to find profile 'stagign', did you mean client/staging?",
+        error.to_string()
+    );
+    assert!(lookup.find("client/prod").is_ok());
+}
+
+#[test]
+fn test_strict_lookup() {
+    let creds_d = crate::utils::homedir().join(".aws").join("credentials.d");
+    let mut lookup = AwsProfileLookup::new();
+
+    for (file, names) in [
+        ("client.ini", vec!["prod", "staging"]),
+        ("work.ini", vec!["prod", "dev"]),
+    ] {
+        let mut credentials_file = AwsCredentialsFile {
+            file: FileSource::from_path(creds_d.join(file)),
+            profiles: IndexMap::new(),
+        };
+
+        for name in names {
+            credentials_file.push(AwsProfile::new(
+                name,
+                "AKIAEXAMPLE",
+                String::new(),
+                None,
+                credentials_file.file.clone(),
+            ));
+        }
+
+        lookup.insert(credentials_file);
+    }
+
+    // unique names and URIs resolve as usual
+    assert_eq!("work/dev", lookup.find_strict("dev").unwrap().uri());
+    assert_eq!(
+        "client/prod",
+        lookup.find_strict("client/prod").unwrap().uri()
+    );
+
+    // ambiguous names list every URI in priority order
+    let error = lookup.find_strict("prod").unwrap_err();
+    let error = error.downcast_ref::<AmbiguousProfile>().unwrap();
+
+    assert_eq!(vec!["work/prod", "client/prod"], error.uris);
+    assert_eq!("work/prod", lookup.find("prod").unwrap().uri());
+
+    let conflicts = lookup.conflicts();
+
+    assert_eq!(1, conflicts.len());
+    assert_eq!("prod", conflicts[0].name);
+    assert_eq!("work/prod", conflicts[0].winner.uri());
+    assert_eq!(
+        vec!["client/prod"],
+        conflicts[0]
+            .shadowed
+            .iter()
+            .map(|p| p.uri())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_non_utf8_file_source() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = crate::utils::homedir().join(".aws").join("credentials.d");
+
+    assert!(!FileSource::from_path(dir.join(OsStr::from_bytes(b"x.\xff"))).encrypted);
+    assert!(FileSource::from_path(dir.join(OsStr::from_bytes(b"\xff.gpg"))).encrypted);
+}
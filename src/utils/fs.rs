@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests;
+
+use crate::utils;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// A file which is written to a temporary sibling and atomically renamed over its target on `commit`.
+///
+/// The temporary file is created exclusively, without following symbolic links and with the given mode, so that its
+/// contents are never exposed with looser permissions. If the `AtomicFile` is dropped without being committed, the
+/// temporary file is removed and the target is left untouched.
+pub struct AtomicFile {
+    target: PathBuf,
+    temporary: PathBuf,
+    file: Option<File>,
+}
+
+impl AtomicFile {
+    /// Start writing a replacement for `target`, refusing targets which exist but aren't regular files.
+    pub fn create<P: AsRef<Path>>(target: P, mode: u32) -> io::Result<Self> {
+        let target = target.as_ref().to_path_buf();
+
+        match fs::symlink_metadata(&target) {
+            Ok(metadata) if !metadata.file_type().is_file() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "refusing to replace {} as it is not a regular file",
+                        target.display()
+                    ),
+                ));
+            }
+            Ok(_) => (),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+
+        let name = target.file_name().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file path", target.display()),
+            )
+        })?;
+
+        let temporary = target.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            utils::random_token(8)?
+        ));
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&temporary)?;
+
+        Ok(Self {
+            target,
+            temporary,
+            file: Some(file),
+        })
+    }
+
+    pub fn target(&self) -> &Path {
+        self.target.as_path()
+    }
+
+    /// Flush the contents to disk and atomically replace the target.
+    pub fn commit(mut self) -> io::Result<()> {
+        let file = self.file.take().unwrap();
+
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&self.temporary, &self.target)
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temporary);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use super::AtomicFile;
+
+use std::io::Write;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn scratch(test: &str) -> PathBuf {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+
+    let dir = std::env::temp_dir().join(format!("aws-env-test-{}-{}", test, nonce));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_commit() {
+    let dir = scratch("atomic-commit");
+    let target = dir.join("file");
+
+    std::fs::write(&target, "old").unwrap();
+
+    let mut file = AtomicFile::create(&target, 0o600).unwrap();
+    file.write_all(b"new").unwrap();
+
+    // the target is untouched until committed
+    assert_eq!("old", std::fs::read_to_string(&target).unwrap());
+
+    file.commit().unwrap();
+
+    assert_eq!("new", std::fs::read_to_string(&target).unwrap());
+    assert_eq!(
+        0o600,
+        std::fs::metadata(&target).unwrap().permissions().mode() & 0o777
+    );
+    assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_abandon() {
+    let dir = scratch("atomic-abandon");
+    let target = dir.join("file");
+
+    let mut file = AtomicFile::create(&target, 0o600).unwrap();
+    file.write_all(b"new").unwrap();
+    drop(file);
+
+    assert!(!target.exists());
+    assert_eq!(0, std::fs::read_dir(&dir).unwrap().count());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_refuses_symlinks() {
+    let dir = scratch("atomic-symlink");
+    let target = dir.join("link");
+
+    std::fs::write(dir.join("file"), "old").unwrap();
+    symlink(dir.join("file"), &target).unwrap();
+
+    assert!(AtomicFile::create(&target, 0o600).is_err());
+    assert!(AtomicFile::create(&dir, 0o600).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
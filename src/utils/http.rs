@@ -0,0 +1,80 @@
+use crate::Error;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+
+use serde::Serialize;
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+/// A synchronous request handler for the small HTTP services emulating AWS credential endpoints.
+pub(crate) trait Handler: Send + Sync + 'static {
+    fn respond(&self, request: &Request<Body>) -> Response<Body>;
+}
+
+/// Serve requests on the given listener with the handler until the `shutdown` future completes.
+pub(crate) async fn serve<H: Handler, F: Future<Output = ()>>(
+    handler: Arc<H>,
+    listener: TcpListener,
+    shutdown: F,
+) -> Result<(), Error> {
+    listener.set_nonblocking(true)?;
+
+    let service = make_service_fn(move |_| {
+        let handler = handler.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let handler = handler.clone();
+
+                async move {
+                    log::debug!("{} {}", request.method(), request.uri().path());
+                    Ok::<_, Infallible>(handler.respond(&request))
+                }
+            }))
+        }
+    });
+
+    Server::from_tcp(listener)?
+        .serve(service)
+        .with_graceful_shutdown(shutdown)
+        .await?;
+
+    Ok(())
+}
+
+/// A plain-text response.
+pub(crate) fn text<S: Into<String>>(body: S) -> Response<Body> {
+    Response::builder()
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(body.into()))
+        .unwrap()
+}
+
+/// A JSON response with the given content type.
+///
+/// NOTE hyper takes ownership of the body, so any secrets it contains can't be zeroized once sent.
+pub(crate) fn json<T: Serialize>(value: &T, content_type: &str) -> Response<Body> {
+    match serde_json::to_vec_pretty(value) {
+        Ok(body) => Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap(),
+        Err(e) => {
+            log::error!("Unable to serialize response: {}", e);
+            status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// An empty response with the given status.
+pub(crate) fn status(code: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::empty())
+        .unwrap()
+}
@@ -1,5 +1,10 @@
+pub(crate) mod fs;
+pub(crate) mod http;
+
 use once_cell::sync::OnceCell;
 
+use std::fs::File;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
 static HOMEDIR: OnceCell<PathBuf> = OnceCell::new();
@@ -8,14 +13,41 @@ static HOMEDIR: OnceCell<PathBuf> = OnceCell::new();
 ///
 /// This internally uses `dirs::home_dir` and a `once_cell::OnceCell`.
 pub(crate) fn homedir() -> &'static PathBuf {
-    HOMEDIR.get_or_init(|| {
-        dirs::home_dir()
-            .expect("unable to get home directory from environment")
-            .to_path_buf()
-    })
+    HOMEDIR.get_or_init(|| dirs::home_dir().expect("unable to get home directory from environment"))
 }
 
-/// Strips the user's home directory from the path and replaces it with `~`
+/// Strips the user's home directory from the path and replaces it with `~`, leaving paths outside of it as they are.
 pub(crate) fn strip_homedir<'a, P: AsRef<Path> + 'a>(p: P) -> PathBuf {
-    Path::new("~").join(p.as_ref().strip_prefix(homedir()).unwrap().to_path_buf())
+    match p.as_ref().strip_prefix(homedir()) {
+        Ok(relative) => Path::new("~").join(relative),
+        Err(_) => p.as_ref().to_path_buf(),
+    }
+}
+
+/// Generate a random, hex-encoded token from the given number of bytes read from `/dev/urandom`.
+pub(crate) fn random_token(bytes: usize) -> io::Result<String> {
+    let mut buffer = vec![0u8; bytes];
+    File::open("/dev/urandom")?.read_exact(buffer.as_mut_slice())?;
+
+    Ok(buffer.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The Levenshtein distance between two strings, counted in characters.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
 }